@@ -0,0 +1,1422 @@
+use crate::cbr::HasId;
+use crate::cbr::text::TfIdf;
+use crate::cbr::{self, CaseSimilarity, InvertedIndex};
+use crate::movie::title::TitleKey;
+use crate::movie::{
+    Company, Credits, DatasetStats, Genre, Keyword, Movie, QueryCase, SimilarityExplanation,
+    SimilarityMeasures, SimilarityWeights,
+};
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::fs::File;
+use std::path::Path;
+use std::str::FromStr;
+
+/// How the similarities to several query movies are combined into one score
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Aggregation {
+    /// Average similarity to the query movies
+    #[default]
+    Mean,
+    /// Similarity to the least similar query movie: fits all of them
+    Min,
+    /// Similarity to the most similar query movie: fits any of them
+    Max,
+    /// Similarity to a synthetic movie in the middle of the query movies
+    Centroid,
+}
+
+impl Aggregation {
+    /// All strategies, in the order they are offered to users
+    pub const ALL: [Aggregation; 4] = [
+        Aggregation::Mean,
+        Aggregation::Min,
+        Aggregation::Max,
+        Aggregation::Centroid,
+    ];
+
+    /// Combines the similarities to each query movie into one score
+    ///
+    /// The centroid is scored as a single movie, so its similarities are
+    /// averaged like `Mean`. Returns 0.0 without any similarity.
+    fn combine(&self, scores: &[f32]) -> f32 {
+        if scores.is_empty() {
+            return 0.0;
+        }
+        match self {
+            Aggregation::Min => scores.iter().copied().fold(f32::INFINITY, f32::min),
+            Aggregation::Max => scores.iter().copied().fold(0.0, f32::max),
+            Aggregation::Mean | Aggregation::Centroid => {
+                scores.iter().sum::<f32>() / scores.len() as f32
+            }
+        }
+    }
+}
+
+impl Display for Aggregation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Aggregation::Mean => "mean",
+            Aggregation::Min => "min",
+            Aggregation::Max => "max",
+            Aggregation::Centroid => "centroid",
+        })
+    }
+}
+
+impl FromStr for Aggregation {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Aggregation::ALL
+            .into_iter()
+            .find(|aggregation| aggregation.to_string().eq_ignore_ascii_case(s))
+            .ok_or_else(|| {
+                format!(
+                    "unknown aggregation `{}` (expected mean, min, max or centroid)",
+                    s
+                )
+            })
+    }
+}
+
+/// A retrieval query: movies the user likes and movies they dislike
+///
+/// Candidates are ranked by their aggregated similarity to the liked movies,
+/// minus `dislike_penalty` times their similarity to the closest disliked
+/// movie. Liked and disliked movies are never returned. The franchise options
+/// keep sequels of the same franchise from filling the results (see
+/// `MovieCaseBase::franchise`).
+#[derive(Debug, Clone, PartialEq)]
+pub struct MovieQuery {
+    /// Indices of the movies to find similar movies to
+    pub liked: Vec<usize>,
+    /// Indices of the movies results should not resemble
+    pub disliked: Vec<usize>,
+    /// How the similarities to several liked movies are combined
+    pub aggregation: Aggregation,
+    /// How strongly resembling a disliked movie lowers the score (0.0 disables it)
+    pub dislike_penalty: f32,
+    /// Return only the best scoring movie of each franchise
+    pub one_per_franchise: bool,
+    /// Leave out every movie of the liked movies' franchises
+    pub hide_query_franchise: bool,
+}
+
+/// An empty query with a moderate dislike penalty
+impl Default for MovieQuery {
+    fn default() -> Self {
+        MovieQuery {
+            liked: Vec::new(),
+            disliked: Vec::new(),
+            aggregation: Aggregation::default(),
+            dislike_penalty: 0.5,
+            one_per_franchise: false,
+            hide_query_franchise: false,
+        }
+    }
+}
+
+/// Collects the distinct items of a set-valued attribute, sorted by name
+fn vocabulary<'a, T: HasId + Clone + 'a>(
+    lists: impl IntoIterator<Item = &'a [T]>,
+    name: fn(&T) -> &str,
+) -> Vec<T> {
+    let mut items: HashMap<u32, T> = HashMap::new();
+    for item in lists.into_iter().flatten() {
+        items.entry(item.id()).or_insert_with(|| item.clone());
+    }
+    let mut items: Vec<T> = items.into_values().collect();
+    items.sort_by(|a, b| name(a).cmp(name(b)).then(a.id().cmp(&b.id())));
+    items
+}
+
+/// Keywords marking a movie as part of a series
+const SERIES_KEYWORDS: &[&str] = &["sequel", "prequel", "spin off", "franchise", "reboot"];
+
+/// Groups the movies into franchises
+///
+/// Movies with the same title stem (see `TitleKey::stem`) belong to the same
+/// franchise when there is more evidence than the title alone: a shared
+/// production company, an installment number in either title, or a series
+/// keyword such as "sequel" on either movie. Two unrelated movies called
+/// "Hamlet" therefore stay apart.
+///
+/// # Returns
+/// * `Vec<usize>` - For every movie, the index of the first movie of its
+///   franchise (its own index for a stand-alone movie)
+fn group_franchises(movies: &[Movie]) -> Vec<usize> {
+    // The display titles, parsed when the case base was indexed
+    let titles: Vec<Option<&TitleKey>> = movies.iter().map(Movie::title_key).collect();
+    let mut by_stem: HashMap<String, Vec<usize>> = HashMap::new();
+    for (movie_idx, title) in titles.iter().enumerate() {
+        let stem = title.map(TitleKey::stem).unwrap_or_default();
+        if !stem.is_empty() {
+            by_stem.entry(stem).or_default().push(movie_idx);
+        }
+    }
+
+    let in_series = |movie_idx: usize| {
+        titles[movie_idx].is_some_and(|title| title.installment().is_some())
+            || movies[movie_idx]
+                .keywords
+                .iter()
+                .any(|keyword| SERIES_KEYWORDS.contains(&keyword.name()))
+    };
+
+    // Union-find over the movie indices, the root being the smallest index
+    let mut parent: Vec<usize> = (0..movies.len()).collect();
+    fn root(parent: &mut [usize], mut movie_idx: usize) -> usize {
+        while parent[movie_idx] != movie_idx {
+            parent[movie_idx] = parent[parent[movie_idx]];
+            movie_idx = parent[movie_idx];
+        }
+        movie_idx
+    }
+    for group in by_stem.values().filter(|group| group.len() > 1) {
+        for (i, &a) in group.iter().enumerate() {
+            for &b in &group[i + 1..] {
+                let related = in_series(a)
+                    || in_series(b)
+                    || cbr::similarity_id(
+                        &movies[a].production_companies,
+                        &movies[b].production_companies,
+                    ) > 0.0;
+                if related {
+                    let (a, b) = (root(&mut parent, a), root(&mut parent, b));
+                    parent[a.max(b)] = a.min(b);
+                }
+            }
+        }
+    }
+    (0..movies.len())
+        .map(|movie_idx| root(&mut parent, movie_idx))
+        .collect()
+}
+
+/// Sorted movie indices without duplicates
+fn distinct(indices: &[usize]) -> Vec<usize> {
+    let mut indices = indices.to_vec();
+    indices.sort_unstable();
+    indices.dedup();
+    indices
+}
+
+/// Opens a CSV file with headers and flexible parsing
+fn csv_reader(path: &Path) -> Result<csv::Reader<File>, Box<dyn std::error::Error>> {
+    // Open the file at the specified path
+    let file = File::open(path)?;
+
+    Ok(csv::ReaderBuilder::new()
+        .has_headers(true)
+        .flexible(true)
+        .from_reader(file))
+}
+
+/// Deserializes the rows of a CSV file, recording the malformed ones
+///
+/// Each row is read as a raw record first, so a rejected row is reported
+/// with its own position rather than the one of the reader.
+///
+/// # Returns
+/// * `Result<Vec<T>, Box<dyn std::error::Error>>` - The valid rows in file order,
+///   or an error if the file itself cannot be read
+fn read_lenient<T: serde::de::DeserializeOwned>(
+    path: &Path,
+    rejected: &mut Vec<RejectedRow>,
+) -> Result<Vec<T>, Box<dyn std::error::Error>> {
+    let mut csv_reader = csv_reader(path)?;
+    let headers = csv_reader.headers()?.clone();
+
+    let mut rows = Vec::new();
+    for result in csv_reader.records() {
+        let record = match result {
+            Ok(record) => record,
+            // I/O errors mean the file cannot be read any further
+            Err(err) if err.is_io_error() => return Err(err.into()),
+            Err(err) => {
+                rejected.push(RejectedRow::from_error(&err, &headers, None));
+                continue;
+            }
+        };
+        match record.deserialize(Some(&headers)) {
+            Ok(row) => rows.push(row),
+            Err(err) => rejected.push(RejectedRow::from_error(&err, &headers, record.position())),
+        }
+    }
+    Ok(rows)
+}
+
+/// Summary of a lenient load: how many rows were loaded and which were dropped
+#[derive(Debug, Clone, Default)]
+pub struct LoadReport {
+    /// Number of movies loaded successfully
+    pub loaded: usize,
+    /// Rows of the movies file that could not be deserialized, in file order
+    pub rejected: Vec<RejectedRow>,
+    /// Rows of the credits file that could not be deserialized, in file order
+    pub rejected_credits: Vec<RejectedRow>,
+}
+
+impl LoadReport {
+    /// Returns true if no row was rejected
+    pub fn is_clean(&self) -> bool {
+        self.rejected.is_empty() && self.rejected_credits.is_empty()
+    }
+
+    /// One-line summary, e.g. "Loaded 4800 movies, skipped 3 malformed rows"
+    ///
+    /// Skipped credits rows are counted separately when there are any.
+    pub fn summary(&self) -> String {
+        let plural = |count: usize| if count == 1 { "" } else { "s" };
+        let mut summary = format!(
+            "Loaded {} movies, skipped {} malformed row{}",
+            self.loaded,
+            self.rejected.len(),
+            plural(self.rejected.len())
+        );
+        if !self.rejected_credits.is_empty() {
+            summary += &format!(
+                " and {} malformed credits row{}",
+                self.rejected_credits.len(),
+                plural(self.rejected_credits.len())
+            );
+        }
+        summary
+    }
+
+    /// Describes every rejected row, movies first, then credits
+    pub fn rejected_rows(&self) -> impl Iterator<Item = String> + '_ {
+        let movies = self.rejected.iter().map(|row| row.to_string());
+        let credits = self
+            .rejected_credits
+            .iter()
+            .map(|row| format!("credits {}", row));
+        movies.chain(credits)
+    }
+}
+
+/// A CSV row rejected by a lenient load
+#[derive(Debug, Clone)]
+pub struct RejectedRow {
+    /// Number of the data row (1-based, not counting the header)
+    pub record: Option<u64>,
+    /// Line number of the row in the file as reported by the CSV reader
+    pub line: Option<u64>,
+    /// Name of the column that failed to deserialize, if known
+    pub column: Option<String>,
+    /// The deserialization error message
+    pub error: String,
+}
+
+impl RejectedRow {
+    /// Extracts the line, column and message of a CSV error
+    ///
+    /// Deserializing an already read record loses its position, so the
+    /// position of the raw record is passed in and preferred over the error's.
+    fn from_error(
+        err: &csv::Error,
+        headers: &csv::StringRecord,
+        position: Option<&csv::Position>,
+    ) -> Self {
+        let position = position.or(err.position());
+        let record = position.map(|position| position.record());
+        let line = position.map(|position| position.line());
+        match err.kind() {
+            csv::ErrorKind::Deserialize { err, .. } => RejectedRow {
+                record,
+                line,
+                column: err
+                    .field()
+                    .and_then(|field| headers.get(field as usize))
+                    .map(str::to_string),
+                error: err.kind().to_string(),
+            },
+            _ => RejectedRow {
+                record,
+                line,
+                column: None,
+                error: err.to_string(),
+            },
+        }
+    }
+}
+
+impl Display for RejectedRow {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (self.record, self.line) {
+            (Some(record), Some(line)) => write!(f, "row {} (line {})", record, line)?,
+            _ => write!(f, "unknown row")?,
+        }
+        if let Some(column) = &self.column {
+            write!(f, ", column `{}`", column)?;
+        }
+        write!(f, ": {}", self.error)
+    }
+}
+
+/// Why a movie was retrieved for a `MovieQuery`
+#[derive(Debug, Clone)]
+pub struct QueryExplanation {
+    /// Final score after the dislike penalty, as ranked by `retrieve`
+    pub score: f32,
+    /// Similarity to the liked movies, combined by the query's aggregation
+    pub liked_score: f32,
+    /// Breakdown against every liked movie, or against their centroid (`None`)
+    pub references: Vec<(Option<usize>, SimilarityExplanation)>,
+    /// The disliked movie most similar to the explained one, with that similarity
+    pub closest_disliked: Option<(usize, f32)>,
+    /// Score taken off for resembling the closest disliked movie
+    pub penalty: f32,
+}
+
+/// The case base of movies together with the retrieval engine
+///
+/// Owns the movie list, the dataset statistics used for normalization and the
+/// active similarity weights. The GUI and the command-line interface are thin
+/// clients of this type, so other crates can use the recommender without
+/// depending on the GUI.
+///
+/// Movies are referred to by their index in `movies()`.
+pub struct MovieCaseBase {
+    movies: Vec<Movie>,                              // All movies in the case base
+    stats: DatasetStats,                             // Numeric distributions (for normalization)
+    weights: SimilarityWeights,                      // Attribute weights of the similarity measures
+    measures: SimilarityMeasures,                    // Local measures of configurable attributes
+    measure: CaseSimilarity<Movie>,                  // Measure built from the weights and stats
+    query_measure: CaseSimilarity<QueryCase, Movie>, // Measure for partial query cases
+    genres: Vec<Genre>,                              // Genre vocabulary, sorted by name
+    keywords: Vec<Keyword>,                          // Keyword vocabulary, sorted by name
+    companies: Vec<Company>,                         // Company vocabulary, sorted by name
+    genre_index: InvertedIndex,                      // Genre id -> movies with that genre
+    keyword_index: InvertedIndex,                    // Keyword id -> movies with that keyword
+    company_index: InvertedIndex,                    // Company id -> movies from that company
+    director_index: InvertedIndex,                   // Director id -> movies by that director
+    cast_index: InvertedIndex,                       // Top-billed actor id -> their movies
+    franchises: Vec<usize>,                          // Movie index -> first movie of its franchise
+    ids: HashMap<u32, usize>,                        // Movie id -> index of the first movie with it
+    exhaustive: bool,                                // Score every movie, not only the candidates
+}
+
+/// An empty case base with default weights
+impl Default for MovieCaseBase {
+    fn default() -> Self {
+        Self::new(Vec::new())
+    }
+}
+
+impl MovieCaseBase {
+    /// Creates a case base from already loaded movies, using the default weights
+    ///
+    /// # Arguments
+    /// * `movies` - The movies of the case base
+    pub fn new(movies: Vec<Movie>) -> Self {
+        let mut case_base = MovieCaseBase {
+            movies,
+            stats: DatasetStats::default(),
+            weights: SimilarityWeights::default(),
+            measures: SimilarityMeasures::default(),
+            measure: CaseSimilarity::new(),
+            query_measure: CaseSimilarity::new(),
+            genres: Vec::new(),
+            keywords: Vec::new(),
+            companies: Vec::new(),
+            genre_index: InvertedIndex::default(),
+            keyword_index: InvertedIndex::default(),
+            company_index: InvertedIndex::default(),
+            director_index: InvertedIndex::default(),
+            cast_index: InvertedIndex::default(),
+            franchises: Vec::new(),
+            ids: HashMap::new(),
+            exhaustive: false,
+        };
+        case_base.reindex();
+        case_base
+    }
+
+    /// Recomputes the text vectors, the dataset statistics, the similarity
+    /// measures, the vocabularies, the inverted indexes and the franchises
+    /// after the movies changed
+    fn reindex(&mut self) {
+        // Fit the TF-IDF model over all overviews and taglines, then vectorize
+        // them, parse the titles and sort the ID lists for the set comparisons
+        let text_model = TfIdf::fit(
+            self.movies
+                .iter()
+                .flat_map(|movie| [movie.overview(), movie.tagline()]),
+        );
+        for movie in &mut self.movies {
+            movie.index_text(&text_model);
+            movie.index_titles();
+            movie.sort_ids();
+        }
+
+        let movies = &self.movies;
+
+        // Collect the numeric attributes across all movies (for normalization)
+        self.stats = DatasetStats::from_movies(movies);
+
+        // Collect the known genres, keywords and companies for query building
+        self.genres = vocabulary(movies.iter().map(|movie| &movie.genres[..]), Genre::name);
+        self.keywords = vocabulary(
+            movies.iter().map(|movie| &movie.keywords[..]),
+            Keyword::name,
+        );
+        self.companies = vocabulary(
+            movies.iter().map(|movie| &movie.production_companies[..]),
+            Company::name,
+        );
+
+        // Index the set-valued attributes for candidate generation
+        self.genre_index = InvertedIndex::from_cases(movies.iter().map(|movie| &movie.genres[..]));
+        self.keyword_index =
+            InvertedIndex::from_cases(movies.iter().map(|movie| &movie.keywords[..]));
+        self.company_index =
+            InvertedIndex::from_cases(movies.iter().map(|movie| &movie.production_companies[..]));
+        let directors: Vec<_> = movies.iter().map(Movie::directors).collect();
+        self.director_index =
+            InvertedIndex::from_cases(directors.iter().map(|directors| &directors[..]));
+        self.cast_index = InvertedIndex::from_cases(movies.iter().map(Movie::top_billed_cast));
+
+        // Group sequels and their originals
+        self.franchises = group_franchises(movies);
+
+        // Look up movies by their TMDB id
+        self.ids = HashMap::new();
+        for (movie_idx, movie) in movies.iter().enumerate() {
+            self.ids.entry(movie.id).or_insert(movie_idx);
+        }
+
+        self.rebuild_measures();
+    }
+
+    /// Loads the case base from a TMDB movies CSV file at the specified path
+    ///
+    /// # Arguments
+    /// * `path` - Path to the CSV file containing movie data
+    ///
+    /// # Returns
+    /// * `Result<MovieCaseBase, Box<dyn std::error::Error>>` - The case base or an error
+    pub fn load(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        // Deserialize the CSV rows into Movie objects, aborting on the first bad row
+        let movies = csv_reader(path)?
+            .deserialize()
+            .collect::<Result<Vec<Movie>, _>>()?;
+
+        Ok(Self::new(movies))
+    }
+
+    /// Loads the case base from a TMDB movies CSV file, skipping malformed rows
+    ///
+    /// Unlike `load`, a row that cannot be deserialized (bad JSON in a `genres`
+    /// cell, a negative budget, a non-numeric `vote_count`, ...) is dropped and
+    /// recorded in the returned report instead of aborting the whole load.
+    ///
+    /// # Arguments
+    /// * `path` - Path to the CSV file containing movie data
+    ///
+    /// # Returns
+    /// * `Result<(MovieCaseBase, LoadReport), Box<dyn std::error::Error>>` - The case
+    ///   base built from the valid rows and the report of rejected rows, or an
+    ///   error if the file itself cannot be read
+    pub fn load_lenient(path: &Path) -> Result<(Self, LoadReport), Box<dyn std::error::Error>> {
+        let mut report = LoadReport::default();
+        let movies: Vec<Movie> = read_lenient(path, &mut report.rejected)?;
+        report.loaded = movies.len();
+
+        Ok((Self::new(movies), report))
+    }
+
+    /// Joins the TMDB credits CSV (cast and crew) to the movies by id
+    ///
+    /// # Arguments
+    /// * `path` - Path to the CSV file containing the credits
+    ///
+    /// # Returns
+    /// * `Result<usize, Box<dyn std::error::Error>>` - The number of movies that
+    ///   received credits, or an error
+    ///
+    /// Credits for movies that are not in the case base are ignored. Director and
+    /// top-billed cast only contribute to the similarity once credits are joined.
+    pub fn load_credits(&mut self, path: &Path) -> Result<usize, Box<dyn std::error::Error>> {
+        let credits = csv_reader(path)?
+            .deserialize()
+            .collect::<Result<Vec<Credits>, _>>()?;
+
+        Ok(self.join_credits(credits))
+    }
+
+    /// Joins the TMDB credits CSV to the movies by id, skipping malformed rows
+    ///
+    /// Like `load_credits`, but a row that cannot be deserialized is dropped and
+    /// recorded in `report.rejected_credits` instead of aborting the join.
+    ///
+    /// # Arguments
+    /// * `path` - Path to the CSV file containing the credits
+    /// * `report` - Report of the movie load, extended with the rejected rows
+    ///
+    /// # Returns
+    /// * `Result<usize, Box<dyn std::error::Error>>` - The number of movies that
+    ///   received credits, or an error if the file itself cannot be read
+    pub fn load_credits_lenient(
+        &mut self,
+        path: &Path,
+        report: &mut LoadReport,
+    ) -> Result<usize, Box<dyn std::error::Error>> {
+        let credits = read_lenient(path, &mut report.rejected_credits)?;
+
+        Ok(self.join_credits(credits))
+    }
+
+    /// Attaches credits to the movies with the same id and rebuilds the indexes
+    fn join_credits(&mut self, credits: Vec<Credits>) -> usize {
+        let mut credits_by_id: HashMap<u32, Credits> = credits
+            .into_iter()
+            .map(|credits| (credits.movie_id, credits))
+            .collect();
+
+        let mut joined = 0;
+        for movie in &mut self.movies {
+            if let Some(credits) = credits_by_id.remove(&movie.id) {
+                movie.set_credits(credits);
+                joined += 1;
+            }
+        }
+
+        self.reindex();
+        joined
+    }
+
+    /// Returns all movies of the case base
+    pub fn movies(&self) -> &[Movie] {
+        &self.movies
+    }
+
+    /// Returns the index of the movie with a TMDB id
+    ///
+    /// # Returns
+    /// * `Option<usize>` - Index of the first movie with that id, or None if
+    ///   the case base has no such movie
+    pub fn index_of(&self, id: u32) -> Option<usize> {
+        self.ids.get(&id).copied()
+    }
+
+    /// Returns the franchise of a movie
+    ///
+    /// Franchises are identified by the index of their first movie, so a
+    /// stand-alone movie is its own franchise (see `group_franchises`).
+    ///
+    /// # Returns
+    /// * `Option<usize>` - The franchise, or None if `idx` is not a movie index
+    pub fn franchise(&self, idx: usize) -> Option<usize> {
+        self.franchises.get(idx).copied()
+    }
+
+    /// Returns the indices of all movies in the franchise of a movie,
+    /// including the movie itself
+    ///
+    /// # Returns
+    /// * `Option<Vec<usize>>` - The members in case base order, or None if
+    ///   `idx` is not a movie index
+    pub fn franchise_members(&self, idx: usize) -> Option<Vec<usize>> {
+        let franchise = self.franchise(idx)?;
+        let members = (franchise..self.movies.len())
+            .filter(|&movie_idx| self.franchises[movie_idx] == franchise)
+            .collect();
+        Some(members)
+    }
+
+    /// Returns the number of movies in the case base
+    pub fn len(&self) -> usize {
+        self.movies.len()
+    }
+
+    /// Returns true if the case base contains no movies
+    pub fn is_empty(&self) -> bool {
+        self.movies.is_empty()
+    }
+
+    /// Returns every genre of the case base, sorted by name
+    pub fn genres(&self) -> &[Genre] {
+        &self.genres
+    }
+
+    /// Returns every keyword of the case base, sorted by name
+    pub fn keywords(&self) -> &[Keyword] {
+        &self.keywords
+    }
+
+    /// Returns every production company of the case base, sorted by name
+    pub fn companies(&self) -> &[Company] {
+        &self.companies
+    }
+
+    /// Returns the numeric attribute distributions used for normalization
+    pub fn stats(&self) -> &DatasetStats {
+        &self.stats
+    }
+
+    /// Returns the attribute weights used for similarity calculation
+    pub fn weights(&self) -> &SimilarityWeights {
+        &self.weights
+    }
+
+    /// Replaces the attribute weights used for similarity calculation
+    ///
+    /// # Arguments
+    /// * `weights` - The new weights (e.g. loaded with `SimilarityWeights::from_path`)
+    pub fn set_weights(&mut self, weights: SimilarityWeights) {
+        self.weights = weights;
+        self.rebuild_measures();
+    }
+
+    /// Returns the local measures of configurable attributes
+    pub fn measures(&self) -> &SimilarityMeasures {
+        &self.measures
+    }
+
+    /// Replaces the local measures of configurable attributes
+    ///
+    /// # Arguments
+    /// * `measures` - The new measures (e.g. loaded with `SimilarityMeasures::from_path`)
+    pub fn set_measures(&mut self, measures: SimilarityMeasures) {
+        self.measures = measures;
+        self.rebuild_measures();
+    }
+
+    /// Rebuilds the similarity measures from the statistics, weights and measures
+    fn rebuild_measures(&mut self) {
+        self.measure = Movie::similarity_measure(&self.stats, &self.weights, &self.measures);
+        self.query_measure =
+            QueryCase::similarity_measure(&self.stats, &self.weights, &self.measures);
+    }
+
+    /// Returns true if retrieval scores every movie instead of only the candidates
+    pub fn is_exhaustive(&self) -> bool {
+        self.exhaustive
+    }
+
+    /// Enables or disables the exhaustive scan
+    ///
+    /// By default only candidate movies sharing at least one genre, keyword,
+    /// production company, director or top-billed actor with the query are
+    /// scored. Movies sharing none of them can still be similar on budget,
+    /// homepage or title, so an exhaustive scan trades speed for completeness.
+    pub fn set_exhaustive(&mut self, exhaustive: bool) {
+        self.exhaustive = exhaustive;
+    }
+
+    /// Finds a movie by its exact title, ignoring case
+    ///
+    /// # Returns
+    /// * `Option<usize>` - Index of the first movie with that title, if any
+    pub fn find_by_title(&self, title: &str) -> Option<usize> {
+        let title = title.to_lowercase();
+        self.movies
+            .iter()
+            .position(|movie| movie.title.to_lowercase() == title)
+    }
+
+    /// Searches movies whose titles contain the query (case insensitive)
+    ///
+    /// # Returns
+    /// * `Vec<usize>` - Indices of the matching movies, in case base order
+    pub fn search(&self, query: &str) -> Vec<usize> {
+        let query = query.to_lowercase();
+        self.movies
+            .iter()
+            .enumerate()
+            .filter(|(_, movie)| movie.title.to_lowercase().contains(&query))
+            .map(|(idx, _)| idx)
+            .collect()
+    }
+
+    /// Retrieves the `k` movies most similar to a query movie
+    ///
+    /// # Arguments
+    /// * `id` - TMDB id of the query movie
+    /// * `k` - Maximum number of movies to return
+    ///
+    /// # Returns
+    /// * `Option<Vec<(usize, f32)>>` - Movie indices with their similarity
+    ///   scores, sorted by similarity in descending order, or None if no movie
+    ///   has that id. The query movie itself is excluded, and movies with equal
+    ///   scores are ordered by ascending movie id.
+    ///
+    /// Unless the case base is exhaustive, only candidate movies are scored
+    /// (see `candidates`).
+    pub fn top_k_similar(&self, id: u32, k: usize) -> Option<Vec<(usize, f32)>> {
+        let idx = self.index_of(id)?;
+        self.top_k_similar_to_all(&[idx], k, Aggregation::Mean)
+    }
+
+    /// Retrieves the k movies most similar to a set of query movies
+    ///
+    /// Every candidate is scored against each query movie and the scores are
+    /// combined with `aggregation`; `Aggregation::Centroid` instead scores the
+    /// candidates against a single synthetic movie built from the queries.
+    /// The query movies themselves are excluded from the results.
+    ///
+    /// # Arguments
+    /// * `queries` - Indices of the query movies (duplicates are ignored)
+    /// * `k` - Maximum number of movies to return
+    /// * `aggregation` - How the similarities to the query movies are combined
+    ///
+    /// # Returns
+    /// * `Option<Vec<(usize, f32)>>` - Movie indices with their aggregated
+    ///   scores, sorted like `top_k_similar`, or None if a query index is not
+    ///   a movie index
+    pub fn top_k_similar_to_all(
+        &self,
+        queries: &[usize],
+        k: usize,
+        aggregation: Aggregation,
+    ) -> Option<Vec<(usize, f32)>> {
+        let query = MovieQuery {
+            liked: queries.to_vec(),
+            aggregation,
+            ..MovieQuery::default()
+        };
+        self.retrieve(&query, k)
+    }
+
+    /// Retrieves the k movies that best match a query
+    ///
+    /// Candidates are scored as in `top_k_similar_to_all` against the liked
+    /// movies. If the query has disliked movies, each score is then lowered
+    /// by the penalty times the highest similarity to a disliked movie (and
+    /// never goes below 0.0). Finally the franchise options of the query
+    /// leave out the liked movies' franchises and keep only the best movie
+    /// of every other franchise.
+    ///
+    /// # Arguments
+    /// * `query` - Liked and disliked movies, aggregation and penalty
+    /// * `k` - Maximum number of movies to return
+    ///
+    /// # Returns
+    /// * `Option<Vec<(usize, f32)>>` - Movie indices with their final scores,
+    ///   sorted like `top_k_similar`, or None if a liked or disliked index is
+    ///   not a movie index
+    pub fn retrieve(&self, query: &MovieQuery, k: usize) -> Option<Vec<(usize, f32)>> {
+        if !self.contains_all(query) {
+            return None;
+        }
+        let mut excluded: Vec<usize> = query.liked.iter().chain(&query.disliked).copied().collect();
+        if query.hide_query_franchise {
+            for &liked_idx in &query.liked {
+                excluded.extend(self.franchise_members(liked_idx)?);
+            }
+        }
+        excluded.sort_unstable();
+        excluded.dedup();
+
+        // Collapsing franchises can leave fewer than k candidates, in which
+        // case every movie is scored instead
+        let mut needed = k + excluded.len();
+        loop {
+            let scored = self.score_query(query, &excluded, needed);
+            if !query.one_per_franchise || scored.len() >= k || needed >= self.movies.len() {
+                let scored = scored
+                    .into_iter()
+                    .map(|(movie_idx, score)| ((self.movies[movie_idx].id, movie_idx), score));
+
+                let ranked = cbr::top_k(scored, k)
+                    .into_iter()
+                    .map(|((_, movie_idx), score)| (movie_idx, score))
+                    .collect();
+                return Some(ranked);
+            }
+            needed = self.movies.len();
+        }
+    }
+
+    /// Returns true if every liked and disliked index of a query is a movie index
+    fn contains_all(&self, query: &MovieQuery) -> bool {
+        query
+            .liked
+            .iter()
+            .chain(&query.disliked)
+            .all(|&movie_idx| movie_idx < self.movies.len())
+    }
+
+    /// Scores the candidates of a query, without the excluded movies
+    ///
+    /// Applies the dislike penalty and, if the query asks for it, keeps only
+    /// the best movie of every franchise.
+    ///
+    /// # Arguments
+    /// * `query` - Liked and disliked movies, aggregation and penalty
+    /// * `excluded` - Sorted indices of the movies left out of the results
+    /// * `needed` - Number of candidates needed, including the excluded movies
+    fn score_query(
+        &self,
+        query: &MovieQuery,
+        excluded: &[usize],
+        needed: usize,
+    ) -> Vec<(usize, f32)> {
+        let mut scored = self.score_liked(&query.liked, needed, query.aggregation);
+        if !query.disliked.is_empty() && query.dislike_penalty > 0.0 {
+            let candidates: Vec<usize> = scored.iter().map(|&(movie_idx, _)| movie_idx).collect();
+            let mut closest = vec![0.0_f32; candidates.len()];
+            for &disliked_idx in &query.disliked {
+                let disliked_scores = self.score(&self.movies[disliked_idx], &candidates);
+                for (closest, (_, similarity)) in closest.iter_mut().zip(disliked_scores) {
+                    *closest = closest.max(similarity);
+                }
+            }
+            for ((_, score), closest) in scored.iter_mut().zip(closest) {
+                *score = (*score - query.dislike_penalty * closest).max(0.0);
+            }
+        }
+
+        let scored: Vec<(usize, f32)> = scored
+            .into_iter()
+            .filter(|(movie_idx, _)| excluded.binary_search(movie_idx).is_err())
+            .collect();
+        if query.one_per_franchise {
+            self.best_per_franchise(scored)
+        } else {
+            scored
+        }
+    }
+
+    /// Keeps the best scoring movie of every franchise
+    ///
+    /// Ties are broken by ascending movie id, like the ranking itself.
+    fn best_per_franchise(&self, scored: Vec<(usize, f32)>) -> Vec<(usize, f32)> {
+        let mut best: HashMap<usize, (usize, f32)> = HashMap::new();
+        for (movie_idx, score) in scored {
+            let franchise = self.franchises[movie_idx];
+            let better = best.get(&franchise).is_none_or(|&(best_idx, best_score)| {
+                score > best_score
+                    || (score == best_score && self.movies[movie_idx].id < self.movies[best_idx].id)
+            });
+            if better {
+                best.insert(franchise, (movie_idx, score));
+            }
+        }
+        best.into_values().collect()
+    }
+
+    /// Scores the candidates of the liked movies, combining the similarities
+    ///
+    /// # Arguments
+    /// * `liked` - Indices of the liked movies (duplicates are ignored)
+    /// * `needed` - Number of candidates needed, including the excluded movies
+    /// * `aggregation` - How the similarities to the liked movies are combined
+    fn score_liked(
+        &self,
+        liked: &[usize],
+        needed: usize,
+        aggregation: Aggregation,
+    ) -> Vec<(usize, f32)> {
+        let liked = distinct(liked);
+        let query_movies: Vec<&Movie> = liked.iter().map(|&idx| &self.movies[idx]).collect();
+        // The candidate fallback already counts the reference movie itself
+        let needed = needed.saturating_sub(1);
+
+        match (aggregation, query_movies.as_slice()) {
+            (_, []) => Vec::new(),
+            (_, [reference_movie]) => {
+                let candidates = self.candidates(reference_movie, needed);
+                self.score(reference_movie, &candidates)
+            }
+            (Aggregation::Centroid, _) => {
+                let centroid = Movie::centroid(&query_movies);
+                let candidates = self.candidates(&centroid, needed);
+                self.score(&centroid, &candidates)
+            }
+            _ => {
+                let mut marked = vec![false; self.movies.len()];
+                for reference_movie in &query_movies {
+                    for movie_idx in self.candidates(reference_movie, needed) {
+                        marked[movie_idx] = true;
+                    }
+                }
+                let candidates: Vec<usize> = (0..self.movies.len())
+                    .filter(|&movie_idx| marked[movie_idx])
+                    .collect();
+
+                let per_query: Vec<Vec<(usize, f32)>> = query_movies
+                    .iter()
+                    .map(|reference_movie| self.score(reference_movie, &candidates))
+                    .collect();
+                candidates
+                    .iter()
+                    .enumerate()
+                    .map(|(i, &movie_idx)| {
+                        let scores: Vec<f32> = per_query.iter().map(|scores| scores[i].1).collect();
+                        (movie_idx, aggregation.combine(&scores))
+                    })
+                    .collect()
+            }
+        }
+    }
+
+    /// Retrieves the k movies that best match a partial query case
+    ///
+    /// Only the attributes set in the query are compared, with the weights
+    /// renormalized over them (see `QueryCase`). Unless the case base is
+    /// exhaustive, only movies sharing a genre, keyword or company with the
+    /// query are scored when the query has any.
+    ///
+    /// # Arguments
+    /// * `query` - The description of the wanted movie
+    /// * `k` - Maximum number of movies to return
+    ///
+    /// # Returns
+    /// * `Vec<(usize, f32)>` - Movie indices with their similarity scores, sorted
+    ///   like `top_k_similar`. Empty if the query sets no attribute.
+    pub fn retrieve_query_case(&self, query: &QueryCase, k: usize) -> Vec<(usize, f32)> {
+        if query.is_empty() {
+            return Vec::new();
+        }
+
+        let mut marked = vec![false; self.movies.len()];
+        self.genre_index.mark_candidates(&query.genres, &mut marked);
+        self.keyword_index
+            .mark_candidates(&query.keywords, &mut marked);
+        self.company_index
+            .mark_candidates(&query.production_companies, &mut marked);
+        let mut candidates: Vec<usize> = (0..self.movies.len())
+            .filter(|&movie_idx| marked[movie_idx])
+            .collect();
+        if self.exhaustive || candidates.len() < k {
+            candidates = (0..self.movies.len()).collect();
+        }
+
+        let scored = self
+            .score_with(&candidates, |movie| {
+                self.query_measure.similarity(query, movie)
+            })
+            .into_iter()
+            .map(|(movie_idx, score)| ((self.movies[movie_idx].id, movie_idx), score));
+
+        cbr::top_k(scored, k)
+            .into_iter()
+            .map(|((_, movie_idx), score)| (movie_idx, score))
+            .collect()
+    }
+
+    /// Generates the movies worth scoring against a reference movie
+    ///
+    /// Candidates are the movies sharing at least one genre, keyword, production
+    /// company, director or top-billed actor with the reference, found through
+    /// the inverted indexes. Falls back to every movie when the case base is
+    /// exhaustive or when there are not enough candidates to fill the `k` results.
+    ///
+    /// # Returns
+    /// * `Vec<usize>` - Candidate movie indices in ascending order
+    fn candidates(&self, reference_movie: &Movie, k: usize) -> Vec<usize> {
+        if !self.exhaustive {
+            let mut marked = vec![false; self.movies.len()];
+            self.genre_index
+                .mark_candidates(&reference_movie.genres, &mut marked);
+            self.keyword_index
+                .mark_candidates(&reference_movie.keywords, &mut marked);
+            self.company_index
+                .mark_candidates(&reference_movie.production_companies, &mut marked);
+            self.director_index
+                .mark_candidates(&reference_movie.directors(), &mut marked);
+            self.cast_index
+                .mark_candidates(reference_movie.top_billed_cast(), &mut marked);
+
+            let candidates: Vec<usize> = marked
+                .iter()
+                .enumerate()
+                .filter(|&(_, &candidate)| candidate)
+                .map(|(movie_idx, _)| movie_idx)
+                .collect();
+            // The reference movie is always among its own candidates
+            if candidates.len() > k {
+                return candidates;
+            }
+        }
+        (0..self.movies.len()).collect()
+    }
+
+    /// Calculates the similarity of the given movies to a reference movie
+    fn score(&self, reference_movie: &Movie, movie_indices: &[usize]) -> Vec<(usize, f32)> {
+        self.score_with(movie_indices, |movie| {
+            self.measure.similarity(movie, reference_movie)
+        })
+    }
+
+    /// Scores the given movies with a similarity function
+    ///
+    /// With the `parallel` feature the movies are scored on the rayon thread pool.
+    /// Results are returned in the order of `movie_indices` either way.
+    fn score_with<F>(&self, movie_indices: &[usize], similarity: F) -> Vec<(usize, f32)>
+    where
+        F: Fn(&Movie) -> f32 + Sync,
+    {
+        #[cfg(feature = "parallel")]
+        let movie_indices = movie_indices.par_iter();
+        #[cfg(not(feature = "parallel"))]
+        let movie_indices = movie_indices.iter();
+
+        movie_indices
+            .map(|&movie_idx| (movie_idx, similarity(&self.movies[movie_idx])))
+            .collect()
+    }
+
+    /// Explains the score of a movie retrieved for a query
+    ///
+    /// With several liked movies, the movie is explained against each of
+    /// them, or against their centroid when the query aggregates by centroid.
+    /// The penalty for resembling a disliked movie is applied as in `retrieve`.
+    ///
+    /// # Arguments
+    /// * `query` - The query the movie was retrieved for
+    /// * `idx` - Index of the movie being explained
+    ///
+    /// # Returns
+    /// * `Option<QueryExplanation>` - The explanation, or None if `idx` or a
+    ///   liked or disliked index is not a movie index
+    pub fn explain_query(&self, query: &MovieQuery, idx: usize) -> Option<QueryExplanation> {
+        if idx >= self.movies.len() || !self.contains_all(query) {
+            return None;
+        }
+        let liked = distinct(&query.liked);
+        let references: Vec<(Option<usize>, SimilarityExplanation)> =
+            if query.aggregation == Aggregation::Centroid && liked.len() > 1 {
+                let query_movies: Vec<&Movie> = liked
+                    .iter()
+                    .map(|&liked_idx| &self.movies[liked_idx])
+                    .collect();
+                let centroid = Movie::centroid(&query_movies);
+                let explanation =
+                    self.movies[idx].explain(&centroid, &self.stats, &self.weights, &self.measures);
+                vec![(None, explanation)]
+            } else {
+                liked
+                    .iter()
+                    .map(|&liked_idx| {
+                        let explanation = self.movies[idx].explain(
+                            &self.movies[liked_idx],
+                            &self.stats,
+                            &self.weights,
+                            &self.measures,
+                        );
+                        (Some(liked_idx), explanation)
+                    })
+                    .collect()
+            };
+
+        let scores: Vec<f32> = references
+            .iter()
+            .map(|(_, explanation)| explanation.score)
+            .collect();
+        let liked_score = query.aggregation.combine(&scores);
+
+        let movie = &self.movies[idx];
+        let closest_disliked = distinct(&query.disliked)
+            .into_iter()
+            .map(|disliked_idx| {
+                let similarity = self.measure.similarity(movie, &self.movies[disliked_idx]);
+                (disliked_idx, similarity)
+            })
+            .fold(
+                None,
+                |closest: Option<(usize, f32)>, candidate| match closest {
+                    Some(closest) if closest.1 >= candidate.1 => Some(closest),
+                    _ => Some(candidate),
+                },
+            );
+        let penalty = match closest_disliked {
+            Some((_, similarity)) if query.dislike_penalty > 0.0 => {
+                query.dislike_penalty * similarity
+            }
+            _ => 0.0,
+        };
+
+        Some(QueryExplanation {
+            score: (liked_score - penalty).max(0.0),
+            liked_score,
+            references,
+            closest_disliked,
+            penalty,
+        })
+    }
+
+    /// Explains the similarity between two movies of the case base
+    ///
+    /// # Arguments
+    /// * `reference_idx` - Index of the query movie
+    /// * `idx` - Index of the movie being explained
+    ///
+    /// # Returns
+    /// * `Option<SimilarityExplanation>` - The explanation, or None if either
+    ///   index is not a movie index
+    pub fn explain(&self, reference_idx: usize, idx: usize) -> Option<SimilarityExplanation> {
+        let explanation = self.movies.get(idx)?.explain(
+            self.movies.get(reference_idx)?,
+            &self.stats,
+            &self.weights,
+            &self.measures,
+        );
+        Some(explanation)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Writes a CSV fixture to a temporary file named after the test
+    fn fixture(name: &str, contents: &str) -> std::path::PathBuf {
+        let path =
+            std::env::temp_dir().join(format!("movie_cbr_{}_{}.csv", name, std::process::id()));
+        std::fs::write(&path, contents).unwrap();
+        path
+    }
+
+    const MOVIE_HEADER: &str = "budget,genres,homepage,id,keywords,original_language,\
+        original_title,overview,popularity,production_companies,production_countries,\
+        release_date,revenue,runtime,spoken_languages,status,tagline,title,vote_average,\
+        vote_count";
+
+    fn movie_row(id: &str, budget: &str) -> String {
+        format!(
+            "{},[],,{},[],en,Movie {},,1.0,[],[],2000-01-01,0,90,[],Released,,Movie {},6.0,10",
+            budget, id, id, id
+        )
+    }
+
+    /// A movie row with a title, a genre and optionally a production company
+    fn titled_row(id: u32, title: &str, genre: &str, company: Option<&str>) -> String {
+        let company = company
+            .map(|name| format!(r#""[{{""name"": ""{}"", ""id"": {}}}]""#, name, name.len()))
+            .unwrap_or_else(|| "[]".to_string());
+        format!(
+            r#"1000,"[{{""id"": {}, ""name"": ""{}""}}]",,{},[],en,{},,1.0,{},[],2000-01-01,0,90,[],Released,,{},6.0,10"#,
+            genre.len(),
+            genre,
+            id,
+            title,
+            company,
+            title
+        )
+    }
+
+    /// Saga, Saga 2 and Saga 3 form a franchise; the two Hamlets do not, as
+    /// neither is numbered nor do they share a company
+    fn franchise_case_base(name: &str) -> MovieCaseBase {
+        let rows = [
+            MOVIE_HEADER.to_string(),
+            titled_row(1, "Saga", "Action", Some("Studio A")),
+            titled_row(2, "Saga 2", "Action", None),
+            titled_row(3, "Saga 3", "Action", None),
+            titled_row(4, "Hamlet", "Drama", Some("Studio A")),
+            titled_row(5, "Hamlet", "Drama", Some("Studio Bee")),
+            titled_row(6, "Other", "Action", None),
+            titled_row(7, "Another", "Drama", None),
+        ];
+        let path = fixture(name, &(rows.join("\n") + "\n"));
+        let result = MovieCaseBase::load(&path);
+        std::fs::remove_file(&path).unwrap();
+        result.unwrap()
+    }
+
+    #[test]
+    fn numbered_titles_with_the_same_stem_form_a_franchise() {
+        let case_base = franchise_case_base("group_franchises");
+        assert_eq!(group_franchises(case_base.movies()), [0, 0, 0, 3, 4, 5, 6]);
+        assert_eq!(case_base.franchise_members(2), Some(vec![0, 1, 2]));
+        assert_eq!(case_base.franchise_members(4), Some(vec![4]));
+    }
+
+    #[test]
+    fn one_per_franchise_still_fills_k_results() {
+        let case_base = franchise_case_base("one_per_franchise");
+        // Only the Sagas share a genre with "Other", and they collapse into one
+        let query = MovieQuery {
+            liked: vec![5],
+            one_per_franchise: true,
+            ..MovieQuery::default()
+        };
+        let retrieved: Vec<usize> = case_base
+            .retrieve(&query, 3)
+            .unwrap()
+            .into_iter()
+            .map(|(movie_idx, _)| movie_idx)
+            .collect();
+        assert_eq!(retrieved.len(), 3);
+        let sagas = retrieved.iter().filter(|&&movie_idx| movie_idx < 3).count();
+        assert_eq!(sagas, 1, "{:?}", retrieved);
+    }
+
+    #[test]
+    fn hide_query_franchise_leaves_out_every_member() {
+        let case_base = franchise_case_base("hide_query_franchise");
+        let mut query = MovieQuery {
+            liked: vec![1],
+            ..MovieQuery::default()
+        };
+        let mut retrieved: Vec<usize> = case_base
+            .retrieve(&query, 10)
+            .unwrap()
+            .into_iter()
+            .map(|(movie_idx, _)| movie_idx)
+            .collect();
+        retrieved.sort_unstable();
+        assert_eq!(retrieved, [0, 2, 3, 4, 5, 6]);
+
+        query.hide_query_franchise = true;
+        let mut retrieved: Vec<usize> = case_base
+            .retrieve(&query, 10)
+            .unwrap()
+            .into_iter()
+            .map(|(movie_idx, _)| movie_idx)
+            .collect();
+        retrieved.sort_unstable();
+        assert_eq!(retrieved, [3, 4, 5, 6]);
+    }
+
+    #[test]
+    fn candidates_give_the_same_top_k_as_the_exhaustive_scan() {
+        let mut case_base = franchise_case_base("candidates");
+        let ids: Vec<u32> = case_base.movies().iter().map(|movie| movie.id).collect();
+        let top_k = |case_base: &MovieCaseBase| -> Vec<Vec<(usize, f32)>> {
+            ids.iter()
+                .map(|&id| case_base.top_k_similar(id, 2).unwrap())
+                .collect()
+        };
+
+        let from_candidates = top_k(&case_base);
+        case_base.set_exhaustive(true);
+        assert_eq!(from_candidates, top_k(&case_base));
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn parallel_scoring_matches_a_sequential_scan() {
+        let mut rows = vec![MOVIE_HEADER.to_string()];
+        for id in 1..=200 {
+            rows.push(movie_row(&id.to_string(), &(id % 17 * 1000).to_string()));
+        }
+        let path = fixture("parallel", &(rows.join("\n") + "\n"));
+        let result = MovieCaseBase::load(&path);
+        std::fs::remove_file(&path).unwrap();
+        let case_base = result.unwrap();
+
+        let movies = case_base.movies();
+        let all: Vec<usize> = (0..movies.len()).collect();
+        for (reference_idx, reference) in movies.iter().enumerate().step_by(20) {
+            let sequential: Vec<(usize, f32)> = all
+                .iter()
+                .map(|&movie_idx| {
+                    let similarity = case_base.measure.similarity(&movies[movie_idx], reference);
+                    (movie_idx, similarity)
+                })
+                .collect();
+            assert_eq!(case_base.score(reference, &all), sequential);
+
+            let expected: Vec<(usize, f32)> = cbr::top_k(
+                sequential
+                    .into_iter()
+                    .filter(|&(movie_idx, _)| movie_idx != reference_idx)
+                    .map(|(movie_idx, score)| ((movies[movie_idx].id, movie_idx), score)),
+                10,
+            )
+            .into_iter()
+            .map(|((_, movie_idx), score)| (movie_idx, score))
+            .collect();
+            assert_eq!(case_base.top_k_similar(reference.id, 10), Some(expected));
+        }
+    }
+
+    #[test]
+    fn unknown_movies_are_rejected_instead_of_panicking() {
+        let case_base = franchise_case_base("unknown_movies");
+        let similar = case_base.top_k_similar(6, 10).unwrap();
+        assert!(similar.iter().all(|&(movie_idx, _)| movie_idx != 5));
+        assert_eq!(case_base.top_k_similar(99, 10), None);
+
+        let query = MovieQuery {
+            liked: vec![0],
+            disliked: vec![7],
+            ..MovieQuery::default()
+        };
+        assert_eq!(case_base.retrieve(&query, 10), None);
+        assert!(case_base.explain_query(&query, 1).is_none());
+        assert!(case_base.explain(0, 7).is_none());
+        assert_eq!(case_base.franchise(7), None);
+        assert_eq!(case_base.franchise_members(7), None);
+    }
+
+    #[test]
+    fn lenient_load_reports_the_file_line_of_rejected_rows() {
+        let rows = [
+            MOVIE_HEADER.to_string(),
+            movie_row("1", "-5"),
+            movie_row("2", "1000"),
+            movie_row("3", "not a number"),
+            movie_row("4", "2000"),
+        ];
+        let path = fixture("rejected_rows", &(rows.join("\n") + "\n"));
+        let result = MovieCaseBase::load_lenient(&path);
+        std::fs::remove_file(&path).unwrap();
+
+        let (case_base, report) = result.unwrap();
+        assert_eq!(case_base.len(), 2);
+        assert_eq!(report.loaded, 2);
+        let positions: Vec<_> = report
+            .rejected
+            .iter()
+            .map(|row| (row.record, row.line, row.column.as_deref()))
+            .collect();
+        assert_eq!(
+            positions,
+            [
+                (Some(1), Some(2), Some("budget")),
+                (Some(3), Some(4), Some("budget")),
+            ]
+        );
+    }
+
+    #[test]
+    fn query_explanation_matches_the_retrieved_score() {
+        let mut rows = vec![MOVIE_HEADER.to_string()];
+        for (id, budget) in ["1000", "2000", "4000", "8000", "16000", "32000"]
+            .iter()
+            .enumerate()
+        {
+            rows.push(movie_row(&(id + 1).to_string(), budget));
+        }
+        let path = fixture("query_explanation", &(rows.join("\n") + "\n"));
+        let result = MovieCaseBase::load(&path);
+        std::fs::remove_file(&path).unwrap();
+        let case_base = result.unwrap();
+
+        for aggregation in Aggregation::ALL {
+            let query = MovieQuery {
+                liked: vec![0, 1],
+                disliked: vec![5, 4],
+                aggregation,
+                dislike_penalty: 0.5,
+                ..MovieQuery::default()
+            };
+            let retrieved = case_base.retrieve(&query, 10).unwrap();
+            assert_eq!(retrieved.len(), 2);
+            for (movie_idx, score) in retrieved {
+                let explanation = case_base.explain_query(&query, movie_idx).unwrap();
+                assert!(
+                    (explanation.score - score).abs() < 1e-6,
+                    "{:?}",
+                    aggregation
+                );
+                assert_eq!(explanation.closest_disliked.map(|(idx, _)| idx), Some(4));
+                assert!(explanation.penalty > 0.0);
+                let references = if aggregation == Aggregation::Centroid {
+                    1
+                } else {
+                    2
+                };
+                assert_eq!(explanation.references.len(), references);
+            }
+        }
+    }
+}
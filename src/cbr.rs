@@ -1,7 +1,12 @@
-// Import the levenshtein function from the strsim crate, which calculates
-// the Levenshtein distance between two strings (the minimum number of single-character
-// edits required to change one string into the other)
-use strsim::levenshtein;
+use chrono::{Datelike, NaiveDate};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+pub mod numeric;
+pub mod string;
+pub mod taxonomy;
+pub mod text;
 
 /// Calculates a similarity score between two numbers within a given range
 ///
@@ -18,10 +23,12 @@ use strsim::levenshtein;
 ///
 /// The function normalizes the absolute difference between a and b by dividing it
 /// by the range (max - min), then subtracts from 1.0 to convert from a distance
-/// to a similarity score.
+/// to a similarity score. An empty range gives 1.0 for equal numbers and 0.0
+/// otherwise, and the bounds may be given in either order.
+///
+/// See the `numeric` module for other numeric types and kernels.
 pub fn similarity_number(a: u32, b: u32, max: u32, min: u32) -> f32 {
-    let diff = (a as f32 - b as f32).abs() / (max - min) as f32;
-    1.0 - diff
+    numeric::similarity_linear(a, b, max, min)
 }
 
 /// Calculates a similarity score between two strings using Levenshtein distance
@@ -37,15 +44,85 @@ pub fn similarity_number(a: u32, b: u32, max: u32, min: u32) -> f32 {
 ///
 /// Special case: If both strings are empty, they are considered identical (1.0).
 /// For all other cases, the function calculates the Levenshtein distance between
-/// the strings and normalizes it by dividing by the length of the longer string,
-/// then subtracts from 1.0 to convert from a distance to a similarity score.
+/// the strings and normalizes it by dividing by the number of characters of the
+/// longer string, then subtracts from 1.0 to convert from a distance to a
+/// similarity score. Accents and other non-ASCII characters count as one
+/// character each, and the strings are NFC-normalized first.
+///
+/// See the `string` module for other metrics, grapheme-based comparison and
+/// case folding.
 pub fn similarity_string(a: &str, b: &str) -> f32 {
-    if a.is_empty() && b.is_empty() {
-        return 1.0;
+    string::similarity_levenshtein(a, b)
+}
+
+/// IDs of a collection in ascending order without duplicates
+///
+/// Collections already sorted by strictly increasing ID (as the genres,
+/// keywords and companies of a movie are once the case base is loaded) are
+/// read in place; only unsorted collections are copied and sorted.
+enum SortedIds<'a, T> {
+    /// The items themselves, already in ID order
+    Items(&'a [T]),
+    /// The sorted, deduplicated IDs of unsorted items
+    Ids(Vec<u32>),
+}
+
+impl<'a, T: HasId> SortedIds<'a, T> {
+    fn new(items: &'a [T]) -> Self {
+        if items.windows(2).all(|pair| pair[0].id() < pair[1].id()) {
+            SortedIds::Items(items)
+        } else {
+            let mut ids: Vec<u32> = items.iter().map(HasId::id).collect();
+            ids.sort_unstable();
+            ids.dedup();
+            SortedIds::Ids(ids)
+        }
+    }
+
+    fn len(&self) -> usize {
+        match self {
+            SortedIds::Items(items) => items.len(),
+            SortedIds::Ids(ids) => ids.len(),
+        }
+    }
+
+    fn get(&self, position: usize) -> Option<u32> {
+        match self {
+            SortedIds::Items(items) => items.get(position).map(HasId::id),
+            SortedIds::Ids(ids) => ids.get(position).copied(),
+        }
+    }
+}
+
+/// Walks the distinct IDs of two collections in ascending order
+///
+/// Calls `visit(id, in_a, in_b)` once per ID found in either collection. The
+/// IDs are merged like two sorted lists, without hashing.
+fn merge_ids<T: HasId>(a: &[T], b: &[T], mut visit: impl FnMut(u32, bool, bool)) {
+    let (a, b) = (SortedIds::new(a), SortedIds::new(b));
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() || j < b.len() {
+        match (a.get(i), b.get(j)) {
+            (Some(x), Some(y)) if x == y => {
+                visit(x, true, true);
+                i += 1;
+                j += 1;
+            }
+            (Some(x), Some(y)) if x < y => {
+                visit(x, true, false);
+                i += 1;
+            }
+            (Some(x), None) => {
+                visit(x, true, false);
+                i += 1;
+            }
+            (_, Some(y)) => {
+                visit(y, false, true);
+                j += 1;
+            }
+            (None, None) => break,
+        }
     }
-    let max_len = a.len().max(b.len()) as f32;
-    let distance = levenshtein(a, b) as f32;
-    1.0 - (distance / max_len)
 }
 
 /// Calculates a similarity score between two collections of items by comparing their IDs
@@ -60,28 +137,176 @@ pub fn similarity_string(a: &str, b: &str) -> f32 {
 ///   - 1.0 means all IDs are identical in both collections
 ///   - 0.0 means there are no common IDs between the collections
 ///
-/// The function extracts IDs from both collections, calculates the size of their
-/// intersection and union, and returns the ratio intersection/union.
+/// The function merges the sorted IDs of both collections, counting the size
+/// of their intersection and union, and returns the ratio intersection/union.
+/// Collections sorted by ID are compared without allocating.
 /// Special case: If the union is empty (both collections are empty), returns 0.0.
 pub fn similarity_id<T: HasId>(a: &[T], b: &[T]) -> f32 {
-    use std::collections::HashSet;
-
-    // Extract IDs from both collections into HashSets for efficient operations
-    let a_ids: HashSet<u32> = a.iter().map(|item| item.id()).collect();
-    let b_ids: HashSet<u32> = b.iter().map(|item| item.id()).collect();
-
-    // Calculate intersection (IDs that appear in both collections)
-    let intersection: HashSet<_> = a_ids.intersection(&b_ids).collect();
-    // Calculate union (unique IDs that appear in either collection)
-    let union: HashSet<_> = a_ids.union(&b_ids).collect();
+    // Count intersection (IDs that appear in both collections) and union
+    // (unique IDs that appear in either collection)
+    let (mut intersection, mut union) = (0, 0);
+    merge_ids(a, b, |_, in_a, in_b| {
+        union += 1;
+        if in_a && in_b {
+            intersection += 1;
+        }
+    });
 
     // Return Jaccard index (intersection over union)
     // If both collections are empty (union is empty), return 0.0
-    if union.is_empty() {
+    if union == 0 {
+        0.0
+    } else {
+        intersection as f32 / union as f32
+    }
+}
+
+/// Calculates a similarity score between two collections of items, weighting
+/// each ID by its rarity (weighted Jaccard index)
+///
+/// Parameters:
+/// - a: First collection of items that implement the HasId trait
+/// - b: Second collection of items that implement the HasId trait
+/// - weights: Weight of each ID (e.g. its IDF across the case base)
+///
+/// Returns:
+/// - A float value between 0.0 and 1.0, where:
+///   - 1.0 means all IDs are identical in both collections
+///   - 0.0 means there are no common IDs between the collections
+///
+/// Like similarity_id, with the summed weights of the common and of all IDs
+/// instead of their counts, so sharing a rare item counts more than sharing a
+/// common one. Special case: If both collections are empty, returns 0.0.
+pub fn similarity_weighted_id<T: HasId>(a: &[T], b: &[T], weights: &IdWeights) -> f32 {
+    // Sum the weights of the common IDs and of all IDs
+    let (mut intersection, mut union) = (0.0, 0.0);
+    merge_ids(a, b, |id, in_a, in_b| {
+        let weight = weights.weight(id);
+        union += weight;
+        if in_a && in_b {
+            intersection += weight;
+        }
+    });
+    if union == 0.0 {
         0.0
     } else {
-        intersection.len() as f32 / union.len() as f32
+        intersection / union
+    }
+}
+
+/// Calculates how much of a query collection is covered by a case collection
+///
+/// Parameters:
+/// - query: Items asked for (e.g. the genres picked in a search form)
+/// - case: Items of the case being compared
+///
+/// Returns:
+/// - A float value between 0.0 and 1.0, where:
+///   - 1.0 means the case contains every queried ID
+///   - 0.0 means the case contains none of them
+///
+/// Unlike similarity_id this measure is asymmetric: extra items in the case
+/// are not penalised, so a query for "Animation" fully matches an animated
+/// family comedy. Special case: an empty query returns 0.0.
+pub fn similarity_coverage<T: HasId>(query: &[T], case: &[T]) -> f32 {
+    let (mut covered, mut total) = (0, 0);
+    merge_ids(query, case, |_, in_query, in_case| {
+        if in_query {
+            total += 1;
+            if in_case {
+                covered += 1;
+            }
+        }
+    });
+    if total == 0 {
+        return 0.0;
+    }
+    covered as f32 / total as f32
+}
+
+/// Calculates how much of a query collection is covered by a case collection,
+/// weighting each ID by its rarity
+///
+/// Parameters:
+/// - query: Items asked for
+/// - case: Items of the case being compared
+/// - weights: Weight of each ID (e.g. its IDF across the case base)
+///
+/// Returns:
+/// - The summed weight of the queried IDs the case contains, divided by the
+///   summed weight of all queried IDs. An empty query returns 0.0.
+pub fn similarity_weighted_coverage<T: HasId>(query: &[T], case: &[T], weights: &IdWeights) -> f32 {
+    let (mut covered, mut total) = (0.0, 0.0);
+    merge_ids(query, case, |id, in_query, in_case| {
+        if in_query {
+            let weight = weights.weight(id);
+            total += weight;
+            if in_case {
+                covered += weight;
+            }
+        }
+    });
+    if total == 0.0 {
+        return 0.0;
+    }
+    covered / total
+}
+
+/// Calculates a similarity score between two collections using a local
+/// measure for their items (best-match average)
+///
+/// Parameters:
+/// - a: First collection of items
+/// - b: Second collection of items
+/// - local: Similarity of two single items, between 0.0 and 1.0
+///
+/// Returns:
+/// - A float value between 0.0 and 1.0, where:
+///   - 1.0 means every item has an identical counterpart in the other collection
+///   - 0.0 means no item resembles any item of the other collection
+///
+/// Every item of each collection is matched with its most similar item in the
+/// other collection, and the best similarities of both sides are averaged.
+/// Unlike similarity_id, related items get partial credit (e.g. through a
+/// taxonomy). Special case: If either collection is empty, returns 0.0.
+pub fn similarity_best_match<T>(a: &[T], b: &[T], local: impl Fn(&T, &T) -> f32) -> f32 {
+    if a.is_empty() || b.is_empty() {
+        return 0.0;
+    }
+    let a_to_b: f32 = a.iter().map(|x| best_match(x, b, &local)).sum();
+    let b_to_a: f32 = b.iter().map(|y| best_match(y, a, |y, x| local(x, y))).sum();
+    (a_to_b + b_to_a) / (a.len() + b.len()) as f32
+}
+
+/// Calculates how well a query collection is covered by a case collection,
+/// giving partial credit to related items
+///
+/// Parameters:
+/// - query: Items asked for
+/// - case: Items of the case being compared
+/// - local: Similarity of a queried item and a case item, between 0.0 and 1.0
+///
+/// Returns:
+/// - The mean over the queried items of their best match in the case, like
+///   similarity_coverage with partial credit. An empty query returns 0.0.
+pub fn similarity_best_match_coverage<T>(
+    query: &[T],
+    case: &[T],
+    local: impl Fn(&T, &T) -> f32,
+) -> f32 {
+    if query.is_empty() {
+        return 0.0;
     }
+    let covered: f32 = query.iter().map(|x| best_match(x, case, &local)).sum();
+    covered / query.len() as f32
+}
+
+/// Highest similarity of an item to any item of a collection (0.0 if empty)
+fn best_match<T>(item: &T, others: &[T], local: impl Fn(&T, &T) -> f32) -> f32 {
+    others
+        .iter()
+        .map(|other| local(item, other))
+        .fold(0.0, f32::max)
 }
 
 /// A trait that requires implementing types to provide an ID method
@@ -92,3 +317,652 @@ pub trait HasId {
     /// Returns the unique ID of an object as a u32
     fn id(&self) -> u32;
 }
+
+/// References to items with an ID also have that ID, so collections of
+/// borrowed items (e.g. a filtered view of a list) can be compared too
+impl<T: HasId> HasId for &T {
+    fn id(&self) -> u32 {
+        (*self).id()
+    }
+}
+
+/// Returns the items of `a` whose IDs also appear in `b`
+///
+/// Parameters:
+/// - a: First collection of items that implement the HasId trait
+/// - b: Second collection of items that implement the HasId trait
+///
+/// Returns:
+/// - The shared items, in the order they appear in `a`, without duplicates.
+///   These are the items behind the intersection used by similarity_id.
+pub fn shared_items<T: HasId + Clone>(a: &[T], b: &[T]) -> Vec<T> {
+    use std::collections::HashSet;
+
+    let b_ids: HashSet<u32> = b.iter().map(|item| item.id()).collect();
+    let mut seen = HashSet::new();
+    a.iter()
+        .filter(|item| b_ids.contains(&item.id()) && seen.insert(item.id()))
+        .cloned()
+        .collect()
+}
+
+/// The contribution of a single attribute to a global similarity score
+#[derive(Debug, Clone, PartialEq)]
+pub struct AttributeScore {
+    /// Name of the attribute (e.g. "budget", "genres")
+    pub name: &'static str,
+    /// Local similarity of the attribute, between 0.0 and 1.0
+    /// (None if the attribute is unknown for either case)
+    pub raw: Option<f32>,
+    /// Weight of the attribute in the global similarity
+    pub weight: f32,
+    /// Local similarity multiplied by the weight (0.0 if unknown)
+    pub weighted: f32,
+}
+
+impl AttributeScore {
+    /// Creates a score from a local similarity and its weight
+    pub fn new(name: &'static str, raw: Option<f32>, weight: f32) -> Self {
+        AttributeScore {
+            name,
+            raw,
+            weight,
+            weighted: raw.map_or(0.0, |raw| raw * weight),
+        }
+    }
+}
+
+/// Local similarity measure comparing a query of type Q with a case of type C
+/// on one attribute, returning None when the attribute is unknown for either
+type LocalMeasure<Q, C> = Box<dyn Fn(&Q, &C) -> Option<f32> + Send + Sync>;
+
+/// A named attribute registered on a CaseSimilarity
+///
+/// The extractor and the local similarity measure are combined into a single
+/// closure comparing two cases.
+struct Attribute<Q, C> {
+    /// Name of the attribute, reported in AttributeScore
+    name: &'static str,
+    /// Weight of the attribute in the global similarity
+    weight: f32,
+    /// Local similarity of the attribute between a query and a case
+    measure: LocalMeasure<Q, C>,
+}
+
+/// Global similarity measure for cases of type T, built from weighted attributes
+///
+/// Each attribute is registered with an extractor closure selecting a value from
+/// the case, a local similarity measure comparing two such values (for example
+/// similarity_number, similarity_string or similarity_id) and a weight.
+/// The global similarity is the weighted sum of the local similarities,
+/// normalized by the sum of the weights.
+///
+/// This lets new case types (TV shows, books, ...) reuse the same machinery
+/// instead of wiring the local measures together by hand.
+///
+/// The query usually is a case of the same type `T`. A query of another type
+/// `Q` (for example a partial description of the wanted case) can be compared
+/// with cases of type `C` through a `CaseSimilarity<Q, C>`, registering its
+/// attributes with `attribute_fn` and `optional_attribute_fn`.
+pub struct CaseSimilarity<Q, C = Q> {
+    attributes: Vec<Attribute<Q, C>>,
+}
+
+impl<Q, C> Default for CaseSimilarity<Q, C> {
+    fn default() -> Self {
+        CaseSimilarity {
+            attributes: Vec::new(),
+        }
+    }
+}
+
+impl<T: 'static> CaseSimilarity<T> {
+    /// Registers a weighted attribute
+    ///
+    /// Parameters:
+    /// - name: Name of the attribute, reported in the per-attribute scores
+    /// - weight: Importance of the attribute in the global similarity
+    /// - extract: Closure selecting the attribute value from a case
+    /// - measure: Local similarity measure between two attribute values,
+    ///   returning a value between 0.0 and 1.0
+    ///
+    /// Returns:
+    /// - The similarity measure with the attribute added, for chaining
+    pub fn attribute<V, E, M>(
+        mut self,
+        name: &'static str,
+        weight: f32,
+        extract: E,
+        measure: M,
+    ) -> Self
+    where
+        V: ?Sized,
+        E: Fn(&T) -> &V + Send + Sync + 'static,
+        M: Fn(&V, &V) -> f32 + Send + Sync + 'static,
+    {
+        self.attributes.push(Attribute {
+            name,
+            weight,
+            measure: Box::new(move |a, b| Some(measure(extract(a), extract(b)))),
+        });
+        self
+    }
+
+    /// Registers a weighted attribute whose extracted value may be unknown
+    ///
+    /// Like `attribute`, but the extractor returns None when the case has no
+    /// usable value (e.g. a zero budget or an empty keyword list). If either
+    /// case is unknown, the attribute is dropped from both the weighted sum
+    /// and the total weight.
+    ///
+    /// Parameters:
+    /// - name: Name of the attribute, reported in the per-attribute scores
+    /// - weight: Importance of the attribute in the global similarity
+    /// - extract: Borrows the attribute value from a case, or None if unknown
+    /// - measure: Local similarity between two known values, between 0.0 and 1.0
+    ///
+    /// Returns:
+    /// - The similarity measure with the attribute added, for chaining
+    pub fn optional_attribute<V, E, M>(
+        mut self,
+        name: &'static str,
+        weight: f32,
+        extract: E,
+        measure: M,
+    ) -> Self
+    where
+        V: ?Sized,
+        E: Fn(&T) -> Option<&V> + Send + Sync + 'static,
+        M: Fn(&V, &V) -> f32 + Send + Sync + 'static,
+    {
+        self.attributes.push(Attribute {
+            name,
+            weight,
+            measure: Box::new(move |a, b| Some(measure(extract(a)?, extract(b)?))),
+        });
+        self
+    }
+}
+
+impl<Q: 'static, C: 'static> CaseSimilarity<Q, C> {
+    /// Creates a similarity measure without any attributes
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers a weighted attribute with a measure comparing whole cases
+    ///
+    /// Use this instead of `attribute` when the compared value has to be
+    /// computed from the case rather than borrowed from it.
+    ///
+    /// Parameters:
+    /// - name: Name of the attribute, reported in the per-attribute scores
+    /// - weight: Importance of the attribute in the global similarity
+    /// - measure: Local similarity between two cases, between 0.0 and 1.0
+    ///
+    /// Returns:
+    /// - The similarity measure with the attribute added, for chaining
+    pub fn attribute_fn<M>(mut self, name: &'static str, weight: f32, measure: M) -> Self
+    where
+        M: Fn(&Q, &C) -> f32 + Send + Sync + 'static,
+    {
+        self.attributes.push(Attribute {
+            name,
+            weight,
+            measure: Box::new(move |a, b| Some(measure(a, b))),
+        });
+        self
+    }
+
+    /// Registers a weighted attribute whose value may be unknown
+    ///
+    /// When the measure returns None (the value is missing for either case),
+    /// the attribute is dropped from both the weighted sum and the total
+    /// weight, so missing data neither inflates nor deflates the similarity.
+    ///
+    /// Parameters:
+    /// - name: Name of the attribute, reported in the per-attribute scores
+    /// - weight: Importance of the attribute in the global similarity
+    /// - measure: Local similarity between two cases, between 0.0 and 1.0,
+    ///   or None if it cannot be determined
+    ///
+    /// Returns:
+    /// - The similarity measure with the attribute added, for chaining
+    pub fn optional_attribute_fn<M>(mut self, name: &'static str, weight: f32, measure: M) -> Self
+    where
+        M: Fn(&Q, &C) -> Option<f32> + Send + Sync + 'static,
+    {
+        self.attributes.push(Attribute {
+            name,
+            weight,
+            measure: Box::new(measure),
+        });
+        self
+    }
+
+    /// Sum of the weights of all registered attributes (assuming all are known)
+    pub fn total_weight(&self) -> f32 {
+        self.attributes
+            .iter()
+            .map(|attribute| attribute.weight)
+            .sum()
+    }
+
+    /// Calculates the local similarity of every attribute between a query and a case
+    ///
+    /// Returns:
+    /// - One AttributeScore per registered attribute, in registration order
+    pub fn scores(&self, a: &Q, b: &C) -> Vec<AttributeScore> {
+        self.attributes
+            .iter()
+            .map(|attribute| {
+                AttributeScore::new(attribute.name, (attribute.measure)(a, b), attribute.weight)
+            })
+            .collect()
+    }
+
+    /// Calculates the global similarity between a query and a case
+    ///
+    /// Returns:
+    /// - The weighted sum of the known local similarities divided by the sum of
+    ///   their weights, a value between 0.0 and 1.0. Unknown attributes are left
+    ///   out of both sums. If no weight remains, returns 0.0.
+    pub fn similarity(&self, a: &Q, b: &C) -> f32 {
+        let (result, total_weight) = self
+            .attributes
+            .iter()
+            .filter_map(|attribute| {
+                (attribute.measure)(a, b).map(|raw| (raw * attribute.weight, attribute.weight))
+            })
+            .fold((0.0, 0.0), |(result, total), (weighted, weight)| {
+                (result + weighted, total + weight)
+            });
+
+        if total_weight <= 0.0 {
+            0.0
+        } else {
+            result / total_weight
+        }
+    }
+}
+
+/// A scored case kept in the bounded heap of top_k
+///
+/// Ordered so that a greater candidate is a better one: higher score first,
+/// then lower key, which makes the ranking deterministic on ties. A NaN score
+/// ranks below every other score.
+struct Candidate<K> {
+    key: K,
+    score: f32,
+}
+
+impl<K: Ord> Ord for Candidate<K> {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        let known = |score: f32| !score.is_nan();
+        known(self.score)
+            .cmp(&known(other.score))
+            .then_with(|| self.score.total_cmp(&other.score))
+            .then_with(|| other.key.cmp(&self.key))
+    }
+}
+
+impl<K: Ord> PartialOrd for Candidate<K> {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<K: Ord> PartialEq for Candidate<K> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == std::cmp::Ordering::Equal
+    }
+}
+
+impl<K: Ord> Eq for Candidate<K> {}
+
+/// Selects the k best scored cases without sorting all of them
+///
+/// Parameters:
+/// - scored: Cases identified by a key, together with their similarity score
+/// - k: Maximum number of cases to return
+///
+/// Returns:
+/// - At most k `(key, score)` pairs sorted by score in descending order.
+///   Cases with equal scores are ordered by ascending key, and cases scored
+///   NaN come last.
+///
+/// A bounded min-heap holds the best k cases seen so far, so selecting from
+/// n cases takes O(n log k) time and O(k) memory instead of a full sort.
+pub fn top_k<K: Ord>(scored: impl IntoIterator<Item = (K, f32)>, k: usize) -> Vec<(K, f32)> {
+    use std::cmp::Reverse;
+    use std::collections::BinaryHeap;
+
+    if k == 0 {
+        return Vec::new();
+    }
+
+    // Min-heap of the best candidates: the root is the worst one kept
+    let mut heap = BinaryHeap::with_capacity(k + 1);
+    for (key, score) in scored {
+        let candidate = Candidate { key, score };
+        if heap.len() < k {
+            heap.push(Reverse(candidate));
+        } else if let Some(mut worst) = heap.peek_mut()
+            && candidate > worst.0
+        {
+            *worst = Reverse(candidate);
+        }
+    }
+
+    // Ascending order of Reverse is descending order of the candidates
+    heap.into_sorted_vec()
+        .into_iter()
+        .map(|Reverse(candidate)| (candidate.key, candidate.score))
+        .collect()
+}
+
+/// Inverted index from item IDs to the cases containing them
+///
+/// Built once over a case base for a set-valued attribute (genres, keywords,
+/// production companies, ...). It answers "which cases share at least one ID
+/// with this query" without comparing the query to every case.
+#[derive(Debug, Clone, Default)]
+pub struct InvertedIndex {
+    /// Case indices containing each ID, in ascending order without duplicates
+    postings: HashMap<u32, Vec<usize>>,
+}
+
+impl InvertedIndex {
+    /// Builds the index from the items of every case
+    ///
+    /// Parameters:
+    /// - cases: The items of each case, in case order (the position of a case
+    ///   in this iterator is the index stored in the postings)
+    pub fn from_cases<'a, T: HasId + 'a>(cases: impl IntoIterator<Item = &'a [T]>) -> Self {
+        let mut postings: HashMap<u32, Vec<usize>> = HashMap::new();
+        for (case_idx, items) in cases.into_iter().enumerate() {
+            for item in items {
+                let posting = postings.entry(item.id()).or_default();
+                // Cases are visited in order, so a duplicate ID is always the last entry
+                if posting.last() != Some(&case_idx) {
+                    posting.push(case_idx);
+                }
+            }
+        }
+        InvertedIndex { postings }
+    }
+
+    /// Returns the indices of the cases containing an ID
+    pub fn postings(&self, id: u32) -> &[usize] {
+        self.postings.get(&id).map(Vec::as_slice).unwrap_or(&[])
+    }
+
+    /// Marks every case sharing at least one ID with the given items
+    ///
+    /// Parameters:
+    /// - items: The items of the query case
+    /// - candidates: One flag per case, set to true for each matching case
+    pub fn mark_candidates<T: HasId>(&self, items: &[T], candidates: &mut [bool]) {
+        for item in items {
+            for &case_idx in self.postings(item.id()) {
+                candidates[case_idx] = true;
+            }
+        }
+    }
+}
+
+/// Weights of item IDs by their rarity across a case base (inverse document frequency)
+///
+/// An ID found in few cases (a keyword like "time loop") weighs more than one
+/// found in many ("independent film"). The weight of an ID in `df` of `n`
+/// cases is the smoothed IDF `ln((1 + n) / (1 + df)) + 1`, as in `text::TfIdf`,
+/// so it is always positive; IDs missing from the case base get the weight of
+/// `df = 0`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct IdWeights {
+    /// Weight of each ID found in the case base, shared with the measure closures
+    weights: Arc<HashMap<u32, f32>>,
+    /// Weight of an ID found in no case
+    unseen: f32,
+}
+
+impl IdWeights {
+    /// Computes the weights from the items of every case
+    ///
+    /// Parameters:
+    /// - cases: The items of each case (an ID repeated within a case counts once)
+    pub fn from_cases<'a, T: HasId + 'a>(cases: impl IntoIterator<Item = &'a [T]>) -> Self {
+        let mut document_frequency: HashMap<u32, usize> = HashMap::new();
+        let mut n = 0;
+        for items in cases {
+            n += 1;
+            let ids: HashSet<u32> = items.iter().map(HasId::id).collect();
+            for id in ids {
+                *document_frequency.entry(id).or_default() += 1;
+            }
+        }
+
+        let idf = |df: usize| ((1.0 + n as f32) / (1.0 + df as f32)).ln() + 1.0;
+        IdWeights {
+            weights: Arc::new(
+                document_frequency
+                    .into_iter()
+                    .map(|(id, df)| (id, idf(df)))
+                    .collect(),
+            ),
+            unseen: idf(0),
+        }
+    }
+
+    /// Returns the weight of an ID
+    pub fn weight(&self, id: u32) -> f32 {
+        self.weights.get(&id).copied().unwrap_or(self.unseen)
+    }
+}
+
+/// How the similarity of two dates decays with the time between them
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum DateDecay {
+    /// Decreases linearly from 1.0 (same day) to 0.0 at `span_years` apart
+    Linear { span_years: f32 },
+    /// Halves every `half_life_years`
+    Exponential { half_life_years: f32 },
+    /// 1.0 within the same decade, minus `step` for every decade apart
+    Decade { step: f32 },
+}
+
+/// Movies from the same era score high: the similarity halves every 10 years
+impl Default for DateDecay {
+    fn default() -> Self {
+        DateDecay::Exponential {
+            half_life_years: 10.0,
+        }
+    }
+}
+
+/// Calculates a similarity score between two dates
+///
+/// Parameters:
+/// - a: First date to compare
+/// - b: Second date to compare
+/// - decay: How quickly the similarity decreases with the time between the dates
+///
+/// Returns:
+/// - A float value between 0.0 and 1.0, where:
+///   - 1.0 means the dates are identical (or in the same decade for DateDecay::Decade)
+///   - values close to 0.0 mean the dates are far apart
+///
+/// A non-positive span or half-life only gives credit to identical dates.
+pub fn similarity_date(a: NaiveDate, b: NaiveDate, decay: DateDecay) -> f32 {
+    let years = (a - b).num_days().abs() as f32 / 365.25;
+    let similarity = match decay {
+        DateDecay::Linear { span_years } if span_years > 0.0 => 1.0 - years / span_years,
+        DateDecay::Exponential { half_life_years } if half_life_years > 0.0 => {
+            0.5_f32.powf(years / half_life_years)
+        }
+        DateDecay::Decade { step } => {
+            let decades = (a.year().div_euclid(10) - b.year().div_euclid(10)).abs();
+            1.0 - step * decades as f32
+        }
+        _ => {
+            if a == b {
+                1.0
+            } else {
+                0.0
+            }
+        }
+    };
+    similarity.clamp(0.0, 1.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, Clone, PartialEq)]
+    struct Item(u32);
+
+    impl HasId for Item {
+        fn id(&self) -> u32 {
+            self.0
+        }
+    }
+
+    fn items(ids: &[u32]) -> Vec<Item> {
+        ids.iter().copied().map(Item).collect()
+    }
+
+    #[test]
+    fn set_measures_agree_on_sorted_and_unsorted_lists() {
+        let (sorted_a, sorted_b) = (items(&[1, 2, 3, 5]), items(&[2, 3, 4]));
+        let (unsorted_a, unsorted_b) = (items(&[5, 3, 1, 2, 3]), items(&[4, 2, 3, 2]));
+
+        assert_eq!(similarity_id(&sorted_a, &sorted_b), 2.0 / 5.0);
+        assert_eq!(similarity_id(&unsorted_a, &unsorted_b), 2.0 / 5.0);
+        assert_eq!(similarity_coverage(&sorted_b, &sorted_a), 2.0 / 3.0);
+        assert_eq!(similarity_coverage(&unsorted_b, &unsorted_a), 2.0 / 3.0);
+    }
+
+    #[test]
+    fn set_measures_handle_empty_lists() {
+        let empty: Vec<Item> = Vec::new();
+        assert_eq!(similarity_id(&empty, &empty), 0.0);
+        assert_eq!(similarity_id(&items(&[1]), &empty), 0.0);
+        assert_eq!(similarity_coverage(&empty, &items(&[1])), 0.0);
+        assert_eq!(similarity_id(&items(&[7, 7]), &items(&[7])), 1.0);
+    }
+
+    #[test]
+    fn weighted_measures_favour_rare_ids() {
+        // ID 1 is in every case, ID 2 only in one
+        let cases = [items(&[1, 2]), items(&[1]), items(&[1, 3])];
+        let weights = IdWeights::from_cases(cases.iter().map(Vec::as_slice));
+        assert!(weights.weight(2) > weights.weight(1));
+
+        let share_common = similarity_weighted_id(&items(&[1, 2]), &items(&[1, 3]), &weights);
+        let share_rare = similarity_weighted_id(&items(&[1, 2]), &items(&[2, 3]), &weights);
+        assert!(share_rare > share_common);
+        assert_eq!(
+            similarity_weighted_id(&items(&[3, 1]), &items(&[1, 3]), &weights),
+            1.0
+        );
+
+        let coverage = similarity_weighted_coverage(&items(&[2, 1]), &items(&[2]), &weights);
+        assert!(coverage > 0.5 && coverage < 1.0);
+    }
+
+    #[test]
+    fn top_k_breaks_ties_by_ascending_key() {
+        let scored = [(4, 0.5), (2, 0.9), (3, 0.5), (1, 0.5), (5, 0.1)];
+        assert_eq!(top_k(scored, 3), [(2, 0.9), (1, 0.5), (3, 0.5)]);
+
+        // The input order does not matter
+        let mut reversed = scored;
+        reversed.reverse();
+        assert_eq!(top_k(reversed, 3), top_k(scored, 3));
+        assert_eq!(top_k(scored, 0), []);
+        assert_eq!(top_k(scored, 10).len(), 5);
+    }
+
+    #[test]
+    fn top_k_ranks_nan_scores_last() {
+        let scored = [(1, f32::NAN), (2, 0.2), (3, -f32::NAN), (4, 0.0)];
+        let keys: Vec<u32> = top_k(scored, 4).into_iter().map(|(key, _)| key).collect();
+        assert_eq!(keys, [2, 4, 1, 3]);
+
+        let best: Vec<u32> = top_k(scored, 2).into_iter().map(|(key, _)| key).collect();
+        assert_eq!(best, [2, 4]);
+    }
+
+    #[test]
+    fn date_similarity_halves_every_half_life() {
+        let date = |year, month, day| NaiveDate::from_ymd_opt(year, month, day).unwrap();
+        let decay = DateDecay::default();
+
+        assert_eq!(
+            similarity_date(date(2000, 6, 1), date(2000, 6, 1), decay),
+            1.0
+        );
+        let same_year = similarity_date(date(2000, 1, 1), date(2000, 12, 31), decay);
+        assert!(same_year > 0.93 && same_year < 1.0, "{}", same_year);
+
+        let decade = similarity_date(date(2000, 1, 1), date(2010, 1, 1), decay);
+        assert!((decade - 0.5).abs() < 1e-3, "{}", decade);
+        let two_decades = similarity_date(date(2010, 1, 1), date(1990, 1, 1), decay);
+        assert!((two_decades - 0.25).abs() < 1e-3, "{}", two_decades);
+
+        // A non-positive half-life only credits identical dates
+        let instant = DateDecay::Exponential {
+            half_life_years: 0.0,
+        };
+        assert_eq!(
+            similarity_date(date(2000, 1, 1), date(2000, 1, 1), instant),
+            1.0
+        );
+        assert_eq!(
+            similarity_date(date(2000, 1, 1), date(2000, 1, 2), instant),
+            0.0
+        );
+    }
+
+    #[test]
+    fn unknown_attributes_are_dropped_from_the_weight_total() {
+        struct Toy {
+            colour: u32,
+            size: Option<u32>,
+        }
+        fn equal(a: &u32, b: &u32) -> f32 {
+            if a == b { 1.0 } else { 0.0 }
+        }
+        let measure = CaseSimilarity::new()
+            .attribute("colour", 1.0, |toy: &Toy| &toy.colour, equal)
+            .optional_attribute("size", 3.0, |toy: &Toy| toy.size.as_ref(), equal);
+
+        let sized = Toy {
+            colour: 1,
+            size: Some(10),
+        };
+        let unsized_toy = Toy {
+            colour: 1,
+            size: None,
+        };
+        // Scoring the missing size as 0.0 would give 1.0 / 4.0
+        assert_eq!(measure.similarity(&sized, &unsized_toy), 1.0);
+        let scores = measure.scores(&sized, &unsized_toy);
+        assert_eq!((scores[1].raw, scores[1].weighted), (None, 0.0));
+
+        let other = Toy {
+            colour: 2,
+            size: Some(10),
+        };
+        assert_eq!(measure.similarity(&sized, &other), 0.75);
+
+        // Without any known attribute there is nothing to compare
+        let only_size =
+            CaseSimilarity::new().optional_attribute_fn("size", 1.0, |a: &Toy, b: &Toy| {
+                Some(equal(a.size.as_ref()?, b.size.as_ref()?))
+            });
+        assert_eq!(only_size.similarity(&sized, &unsized_toy), 0.0);
+    }
+}
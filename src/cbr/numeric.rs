@@ -0,0 +1,526 @@
+//! Numeric similarity for attributes such as budget, revenue or runtime
+//!
+//! Two numbers are compared by their distance relative to the range of the
+//! attribute in the case base. A kernel turns that normalized distance into a
+//! similarity: linear, Gaussian or exponential decay. Differences within a
+//! tolerance count as identical.
+//!
+//! Skewed attributes such as budget can be compared on a log scale or by
+//! percentile rank instead, so a few outliers do not squeeze every other value
+//! into a small part of the range.
+//!
+//! Every function returns a value between 0.0 and 1.0, also for degenerate
+//! input: an empty or inverted range, non-finite values or kernel parameters.
+
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+/// A number that can be compared by the numeric similarity measures
+pub trait Numeric: Copy {
+    /// Converts the number to a 64-bit float for the computation
+    fn to_f64(self) -> f64;
+}
+
+macro_rules! impl_numeric {
+    ($($ty:ty),*) => {
+        $(
+            impl Numeric for $ty {
+                fn to_f64(self) -> f64 {
+                    self as f64
+                }
+            }
+        )*
+    };
+}
+
+impl_numeric!(u32, u64, i32, i64, f32, f64);
+
+/// Shape of the similarity curve over the normalized distance
+///
+/// The distance is the absolute difference of the two numbers divided by the
+/// range (max - min), so 1.0 means "as far apart as the whole range".
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum NumericKernel {
+    /// Decreases linearly from 1.0 to 0.0 at the ends of the range
+    Linear,
+    /// Bell curve with the given `width` (standard deviation, as a fraction of the range)
+    Gaussian { width: f32 },
+    /// Halves every `half_distance` (as a fraction of the range)
+    Exponential { half_distance: f32 },
+}
+
+/// The classic min-max comparison
+impl Default for NumericKernel {
+    fn default() -> Self {
+        NumericKernel::Linear
+    }
+}
+
+/// Scale on which the distance between two numbers is measured
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NumericScale {
+    /// The raw values, relative to the min-max range
+    #[default]
+    Linear,
+    /// The logarithm of the values: equal ratios are about equally far apart,
+    /// so $1M vs $5M is farther apart than $100M vs $150M
+    Log,
+    /// The fraction of known values below each value (0.0 to 1.0): two values
+    /// are as far apart as the share of the case base lying between them
+    PercentileRank,
+}
+
+/// The known values of a numeric attribute across the case base
+///
+/// Provides the min-max range and the percentile ranks used by the measures.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct NumericDomain {
+    /// Finite values in ascending order, shared with every measure closure
+    sorted: Arc<[f64]>,
+}
+
+impl NumericDomain {
+    /// Collects the finite values of an attribute
+    pub fn from_values<N: Numeric>(values: impl IntoIterator<Item = N>) -> Self {
+        let mut sorted: Vec<f64> = values
+            .into_iter()
+            .map(Numeric::to_f64)
+            .filter(|value| value.is_finite())
+            .collect();
+        sorted.sort_by(f64::total_cmp);
+        NumericDomain {
+            sorted: sorted.into(),
+        }
+    }
+
+    /// Number of known values
+    pub fn len(&self) -> usize {
+        self.sorted.len()
+    }
+
+    /// Returns true if no value is known
+    pub fn is_empty(&self) -> bool {
+        self.sorted.is_empty()
+    }
+
+    /// Smallest known value, or 0.0 if there is none
+    pub fn min(&self) -> f64 {
+        self.sorted.first().copied().unwrap_or(0.0)
+    }
+
+    /// Largest known value, or 0.0 if there is none
+    pub fn max(&self) -> f64 {
+        self.sorted.last().copied().unwrap_or(0.0)
+    }
+
+    /// Fraction of the known values below the given value, between 0.0 and 1.0
+    ///
+    /// Values equal to the given one count as half below, so the smallest and
+    /// largest of many distinct values get ranks close to 0.0 and 1.0.
+    /// Returns 0.5 if no value is known.
+    pub fn percentile_rank<N: Numeric>(&self, value: N) -> f64 {
+        if self.sorted.is_empty() {
+            return 0.5;
+        }
+        let value = value.to_f64();
+        let below = self.sorted.partition_point(|known| *known < value);
+        let not_above = self.sorted.partition_point(|known| *known <= value);
+        (below as f64 + (not_above - below) as f64 / 2.0) / self.sorted.len() as f64
+    }
+}
+
+/// A configurable local measure for numeric attributes
+#[derive(Debug, Clone, Copy, PartialEq, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct NumericMeasure {
+    /// Shape of the similarity curve
+    pub kernel: NumericKernel,
+    /// Differences up to this value (in the attribute's unit) count as identical
+    pub tolerance: f64,
+    /// Scale on which the distance is measured (used by `similarity_in`)
+    pub scale: NumericScale,
+}
+
+impl NumericMeasure {
+    /// Calculates the similarity of two numbers within a given range
+    ///
+    /// Parameters:
+    /// - a: First number to compare
+    /// - b: Second number to compare
+    /// - max: Maximum possible value in the range
+    /// - min: Minimum possible value in the range
+    ///
+    /// Returns:
+    /// - A float value between 0.0 and 1.0
+    pub fn similarity<N: Numeric>(&self, a: N, b: N, max: N, min: N) -> f32 {
+        match normalized_distance(a, b, max, min, self.tolerance) {
+            Some(distance) => self.kernel_similarity(distance),
+            None => 0.0,
+        }
+    }
+
+    /// Calculates the similarity of two numbers within the domain of their attribute
+    ///
+    /// Unlike `similarity`, this honours the scale of the measure. On the log
+    /// and percentile-rank scales, numbers within the tolerance count as
+    /// identical and the distance of all others is measured on the scale.
+    ///
+    /// Parameters:
+    /// - a: First number to compare
+    /// - b: Second number to compare
+    /// - domain: Known values of the attribute in the case base
+    ///
+    /// Returns:
+    /// - A float value between 0.0 and 1.0
+    pub fn similarity_in<N: Numeric>(&self, a: N, b: N, domain: &NumericDomain) -> f32 {
+        let (a, b) = (a.to_f64(), b.to_f64());
+        let (scaled_a, scaled_b, max, min) = match self.scale {
+            NumericScale::Linear => return self.similarity(a, b, domain.max(), domain.min()),
+            NumericScale::Log => (
+                log_scale(a),
+                log_scale(b),
+                log_scale(domain.max()),
+                log_scale(domain.min()),
+            ),
+            NumericScale::PercentileRank => (
+                domain.percentile_rank(a),
+                domain.percentile_rank(b),
+                1.0,
+                0.0,
+            ),
+        };
+        if (a - b).abs() <= self.tolerance {
+            return 1.0;
+        }
+        let exact = NumericMeasure {
+            tolerance: 0.0,
+            ..*self
+        };
+        exact.similarity(scaled_a, scaled_b, max, min)
+    }
+
+    /// Turns a normalized distance (0.0 to 1.0) into a similarity using the kernel
+    fn kernel_similarity(&self, distance: f64) -> f32 {
+        let similarity = match self.kernel {
+            NumericKernel::Linear => 1.0 - distance,
+            NumericKernel::Gaussian { width } if width > 0.0 && width.is_finite() => {
+                let width = width as f64;
+                (-(distance * distance) / (2.0 * width * width)).exp()
+            }
+            NumericKernel::Exponential { half_distance }
+                if half_distance > 0.0 && half_distance.is_finite() =>
+            {
+                0.5_f64.powf(distance / half_distance as f64)
+            }
+            // A non-positive width or half distance only gives credit to identical values
+            _ => step(distance),
+        };
+        similarity.clamp(0.0, 1.0) as f32
+    }
+}
+
+/// Signed logarithm that keeps 0 at 0 and works for negative numbers
+fn log_scale(value: f64) -> f64 {
+    value.signum() * value.abs().ln_1p()
+}
+
+/// Distance between two numbers relative to the range, after the tolerance
+///
+/// The bounds may be given in either order. If the range is empty the distance
+/// is 0.0 for numbers within the tolerance and 1.0 otherwise. Returns None if
+/// any input is not a finite number.
+fn normalized_distance<N: Numeric>(a: N, b: N, max: N, min: N, tolerance: f64) -> Option<f64> {
+    let (a, b, max, min) = (a.to_f64(), b.to_f64(), max.to_f64(), min.to_f64());
+    if ![a, b, max, min].iter().all(|value| value.is_finite()) {
+        return None;
+    }
+
+    let tolerance = if tolerance.is_finite() {
+        tolerance.max(0.0)
+    } else {
+        0.0
+    };
+    let difference = ((a - b).abs() - tolerance).max(0.0);
+    let range = (max - min).abs();
+    if range == 0.0 {
+        return Some(1.0 - step(difference));
+    }
+    Some((difference / range).min(1.0))
+}
+
+/// 1.0 for a zero distance, 0.0 otherwise
+fn step(distance: f64) -> f64 {
+    if distance == 0.0 { 1.0 } else { 0.0 }
+}
+
+/// Linear similarity of two numbers within a given range
+///
+/// Like `similarity_number`, for any numeric type. Equal numbers score 1.0 and
+/// different ones 0.0 when the range is empty.
+pub fn similarity_linear<N: Numeric>(a: N, b: N, max: N, min: N) -> f32 {
+    NumericMeasure::default().similarity(a, b, max, min)
+}
+
+/// Gaussian similarity of two numbers within a given range
+///
+/// `width` is the standard deviation of the bell curve as a fraction of the range.
+pub fn similarity_gaussian<N: Numeric>(a: N, b: N, max: N, min: N, width: f32) -> f32 {
+    NumericMeasure {
+        kernel: NumericKernel::Gaussian { width },
+        ..Default::default()
+    }
+    .similarity(a, b, max, min)
+}
+
+/// Exponential-decay similarity of two numbers within a given range
+///
+/// The similarity halves every `half_distance` (as a fraction of the range).
+pub fn similarity_exponential<N: Numeric>(a: N, b: N, max: N, min: N, half_distance: f32) -> f32 {
+    NumericMeasure {
+        kernel: NumericKernel::Exponential { half_distance },
+        ..Default::default()
+    }
+    .similarity(a, b, max, min)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Measures covering every kernel, including degenerate parameters
+    fn measures() -> Vec<NumericMeasure> {
+        let kernels = [
+            NumericKernel::Linear,
+            NumericKernel::Gaussian { width: 0.1 },
+            NumericKernel::Gaussian { width: 0.0 },
+            NumericKernel::Gaussian { width: -1.0 },
+            NumericKernel::Gaussian { width: f32::NAN },
+            NumericKernel::Exponential { half_distance: 0.2 },
+            NumericKernel::Exponential { half_distance: 0.0 },
+            NumericKernel::Exponential {
+                half_distance: f32::INFINITY,
+            },
+        ];
+        let tolerances = [0.0, 5.0, -1.0, f64::NAN, f64::INFINITY];
+        kernels
+            .iter()
+            .flat_map(|&kernel| {
+                tolerances.iter().map(move |&tolerance| NumericMeasure {
+                    kernel,
+                    tolerance,
+                    scale: NumericScale::Linear,
+                })
+            })
+            .collect()
+    }
+
+    /// Checks the properties every measure must have on all combinations of the samples
+    fn check_properties<N: Numeric + std::fmt::Debug>(samples: &[N]) {
+        let finite = |value: N| value.to_f64().is_finite();
+        for measure in measures() {
+            for &a in samples {
+                for &b in samples {
+                    for &max in samples {
+                        for &min in samples {
+                            let similarity = measure.similarity(a, b, max, min);
+                            let case = (measure, a, b, max, min);
+                            assert!((0.0..=1.0).contains(&similarity), "{:?}", case);
+                            assert_eq!(
+                                similarity,
+                                measure.similarity(b, a, max, min),
+                                "{:?}",
+                                case
+                            );
+                            assert_eq!(
+                                similarity,
+                                measure.similarity(a, b, min, max),
+                                "{:?}",
+                                case
+                            );
+                            if ![a, b, max, min].into_iter().all(finite) {
+                                assert_eq!(similarity, 0.0, "{:?}", case);
+                            } else if a.to_f64() == b.to_f64() {
+                                assert_eq!(similarity, 1.0, "{:?}", case);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn properties_hold_for_u32() {
+        check_properties(&[0_u32, 1, 7, 1_000, 250_000_000, u32::MAX]);
+    }
+
+    #[test]
+    fn properties_hold_for_u64() {
+        check_properties(&[0_u64, 3, 1 << 40, u64::MAX - 1, u64::MAX]);
+    }
+
+    #[test]
+    fn properties_hold_for_f32() {
+        check_properties(&[
+            -1e9_f32,
+            -1.5,
+            0.0,
+            0.25,
+            3.0,
+            1e30,
+            f32::MAX,
+            f32::NAN,
+            f32::INFINITY,
+            f32::NEG_INFINITY,
+        ]);
+    }
+
+    #[test]
+    fn empty_range_only_matches_equal_numbers() {
+        let linear = measures()
+            .into_iter()
+            .filter(|measure| measure.kernel == NumericKernel::Linear);
+        for measure in linear {
+            let tolerance = if measure.tolerance.is_finite() {
+                measure.tolerance.max(0.0)
+            } else {
+                0.0
+            };
+            assert_eq!(measure.similarity(4.0, 4.0, 10.0, 10.0), 1.0);
+            let expected = if tolerance >= 6.0 { 1.0 } else { 0.0 };
+            assert_eq!(measure.similarity(4.0, 10.0, 10.0, 10.0), expected);
+        }
+    }
+
+    #[test]
+    fn non_positive_kernel_parameters_behave_as_a_step() {
+        for kernel in [
+            NumericKernel::Gaussian { width: 0.0 },
+            NumericKernel::Gaussian { width: -0.5 },
+            NumericKernel::Exponential { half_distance: 0.0 },
+            NumericKernel::Exponential {
+                half_distance: -2.0,
+            },
+        ] {
+            let measure = NumericMeasure {
+                kernel,
+                ..Default::default()
+            };
+            assert_eq!(measure.similarity(50_u32, 50, 100, 0), 1.0);
+            assert_eq!(measure.similarity(50_u32, 51, 100, 0), 0.0);
+        }
+    }
+
+    #[test]
+    fn tolerance_is_subtracted_from_the_difference() {
+        let measure = NumericMeasure {
+            tolerance: 5.0,
+            ..Default::default()
+        };
+        assert_eq!(measure.similarity(10_u32, 15, 100, 0), 1.0);
+        assert_eq!(measure.similarity(10_u32, 35, 100, 0), 0.8);
+        assert!((measure.similarity(0_u32, 100, 100, 0) - 0.05).abs() < 1e-6);
+
+        // A negative or non-finite tolerance is no tolerance at all
+        for tolerance in [-5.0, f64::NAN, f64::INFINITY] {
+            let measure = NumericMeasure {
+                tolerance,
+                ..Default::default()
+            };
+            assert_eq!(measure.similarity(10_u32, 15, 100, 0), 0.95);
+        }
+    }
+
+    #[test]
+    fn kernels_decay_with_the_distance() {
+        let gaussian = NumericMeasure {
+            kernel: NumericKernel::Gaussian { width: 0.1 },
+            ..Default::default()
+        };
+        let exponential = NumericMeasure {
+            kernel: NumericKernel::Exponential { half_distance: 0.2 },
+            ..Default::default()
+        };
+        assert!((gaussian.similarity(0.0, 0.1, 1.0, 0.0) - (-0.5_f32).exp()).abs() < 1e-6);
+        assert!((exponential.similarity(0.0, 0.2, 1.0, 0.0) - 0.5).abs() < 1e-6);
+        assert!((exponential.similarity(0.0, 0.4, 1.0, 0.0) - 0.25).abs() < 1e-6);
+    }
+
+    #[test]
+    fn scaled_similarity_stays_in_range_and_is_symmetric() {
+        let domain = NumericDomain::from_values([0_u64, 1, 10, 1_000, 1_000_000, u64::MAX]);
+        let samples = [0.0, 5.0, 1e6, -3.0, 1e20, f64::NAN, f64::INFINITY];
+        for scale in [
+            NumericScale::Linear,
+            NumericScale::Log,
+            NumericScale::PercentileRank,
+        ] {
+            for measure in measures() {
+                let measure = NumericMeasure { scale, ..measure };
+                for a in samples {
+                    for b in samples {
+                        let similarity = measure.similarity_in(a, b, &domain);
+                        assert!((0.0..=1.0).contains(&similarity), "{:?}", (measure, a, b));
+                        assert_eq!(similarity, measure.similarity_in(b, a, &domain));
+                        if a.is_finite() && a == b {
+                            assert_eq!(similarity, 1.0, "{:?}", (measure, a));
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn only_percentile_rank_separates_small_and_large_budgets_equally() {
+        let domain = NumericDomain::from_values([
+            0_u32,
+            1_000_000,
+            5_000_000,
+            100_000_000,
+            150_000_000,
+            200_000_000,
+            380_000_000,
+        ]);
+        let similarities = |scale| {
+            let measure = NumericMeasure {
+                scale,
+                ..Default::default()
+            };
+            (
+                measure.similarity_in(1_000_000_u32, 5_000_000, &domain),
+                measure.similarity_in(100_000_000_u32, 150_000_000, &domain),
+            )
+        };
+        let close = |a: f32, b: f32| (a - b).abs() < 1e-3;
+
+        // Linear: the blockbusters flatten the small budgets
+        let (small, large) = similarities(NumericScale::Linear);
+        assert!(
+            close(small, 0.989) && close(large, 0.868),
+            "{} {}",
+            small,
+            large
+        );
+
+        // Log: equal ratios are equally far apart, so the small pair is farther
+        let (small, large) = similarities(NumericScale::Log);
+        assert!(
+            close(small, 0.919) && close(large, 0.979),
+            "{} {}",
+            small,
+            large
+        );
+
+        // Percentile rank: both pairs are neighbours in the case base
+        let (small, large) = similarities(NumericScale::PercentileRank);
+        assert!(
+            close(small, 0.857) && close(large, 0.857),
+            "{} {}",
+            small,
+            large
+        );
+    }
+}
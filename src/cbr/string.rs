@@ -0,0 +1,312 @@
+//! String similarity for attributes such as title or homepage
+//!
+//! Strings are compared as sequences of Unicode characters or grapheme
+//! clusters (what a reader sees as one letter, e.g. "é" written as "e" and a
+//! combining accent, or an emoji with a skin tone), never as bytes. Before the
+//! comparison a string can be Unicode-normalized, so composed and decomposed
+//! accents are equal, and case folded.
+//!
+//! Four metrics are available: normalized Levenshtein, Damerau-Levenshtein
+//! (which also counts a swap of two neighbours as one edit), Jaro-Winkler
+//! (which favours a common prefix) and the Dice coefficient of character
+//! n-grams (which tolerates reordered words).
+//!
+//! Every function returns a value between 0.0 and 1.0. Two empty strings are
+//! identical.
+
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::collections::HashMap;
+use unicode_normalization::UnicodeNormalization;
+use unicode_segmentation::UnicodeSegmentation;
+
+/// Algorithm used to compare two sequences of characters
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum StringMetric {
+    /// Insertions, deletions and substitutions, divided by the longer length
+    #[default]
+    Levenshtein,
+    /// Like Levenshtein, a swap of two adjacent characters counts as one edit
+    DamerauLevenshtein,
+    /// Matching characters within a window, boosted by a common prefix of up to 4
+    JaroWinkler,
+    /// Dice coefficient of the multisets of `n` consecutive characters
+    NgramDice { n: usize },
+}
+
+/// What counts as one character in the comparison
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StringUnit {
+    /// Unicode scalar values (`char`)
+    #[default]
+    Chars,
+    /// Extended grapheme clusters, i.e. user-perceived characters
+    Graphemes,
+}
+
+/// Unicode normalization applied before the comparison
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Normalization {
+    /// Compare the strings as they are
+    None,
+    /// Canonical composition: composed and decomposed accents are equal
+    #[default]
+    Nfc,
+    /// Compatibility composition: also folds ligatures, full-width forms,
+    /// superscripts and similar variants ("ﬁ" equals "fi")
+    Nfkc,
+}
+
+/// A configurable local measure for string attributes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct StringMeasure {
+    /// Algorithm comparing the two strings
+    pub metric: StringMetric,
+    /// What counts as one character
+    pub unit: StringUnit,
+    /// Unicode normalization applied first
+    pub normalization: Normalization,
+    /// Compare the strings ignoring case
+    pub case_fold: bool,
+}
+
+impl StringMeasure {
+    /// Calculates the similarity of two strings
+    ///
+    /// Parameters:
+    /// - a: First string to compare
+    /// - b: Second string to compare
+    ///
+    /// Returns:
+    /// - A float value between 0.0 and 1.0, where 1.0 means the strings are
+    ///   equal after normalization and case folding
+    pub fn similarity(&self, a: &str, b: &str) -> f32 {
+        let (a, b) = (self.prepare(a), self.prepare(b));
+        if a == b {
+            return 1.0;
+        }
+        let (a, b) = (self.units(&a), self.units(&b));
+        let similarity = match self.metric {
+            StringMetric::Levenshtein => {
+                normalized_distance(strsim::generic_levenshtein(&a, &b), &a, &b)
+            }
+            StringMetric::DamerauLevenshtein => {
+                normalized_distance(strsim::generic_damerau_levenshtein(&a, &b), &a, &b)
+            }
+            StringMetric::JaroWinkler => strsim::generic_jaro_winkler(&a, &b),
+            StringMetric::NgramDice { n } => ngram_dice(&a, &b, n),
+        };
+        similarity.clamp(0.0, 1.0) as f32
+    }
+
+    /// Applies the normalization and case folding of the measure
+    fn prepare<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        let text: Cow<str> = match self.normalization {
+            Normalization::None => Cow::Borrowed(text),
+            Normalization::Nfc => Cow::Owned(text.nfc().collect()),
+            Normalization::Nfkc => Cow::Owned(text.nfkc().collect()),
+        };
+        if self.case_fold {
+            Cow::Owned(text.to_lowercase())
+        } else {
+            text
+        }
+    }
+
+    /// Splits a string into the units (chars or graphemes) of the measure
+    fn units<'a>(&self, text: &'a str) -> Vec<&'a str> {
+        match self.unit {
+            StringUnit::Chars => text
+                .char_indices()
+                .map(|(start, c)| &text[start..start + c.len_utf8()])
+                .collect(),
+            StringUnit::Graphemes => text.graphemes(true).collect(),
+        }
+    }
+}
+
+/// Turns an edit distance into a similarity relative to the longer sequence
+fn normalized_distance(distance: usize, a: &[&str], b: &[&str]) -> f64 {
+    let longest = a.len().max(b.len());
+    if longest == 0 {
+        return 1.0;
+    }
+    1.0 - distance as f64 / longest as f64
+}
+
+/// Dice coefficient of the n-gram multisets of two sequences
+///
+/// Sequences shorter than `n` are taken as a single n-gram, so short strings
+/// only match themselves. An `n` of 0 is treated as 1.
+fn ngram_dice(a: &[&str], b: &[&str], n: usize) -> f64 {
+    let n = n.max(1);
+    let grams = |units: &[&str]| -> Vec<String> {
+        if units.len() < n {
+            vec![units.concat()]
+        } else {
+            units.windows(n).map(|gram| gram.concat()).collect()
+        }
+    };
+    let (a, b) = (grams(a), grams(b));
+
+    let mut counts: HashMap<&str, usize> = HashMap::new();
+    for gram in &a {
+        *counts.entry(gram).or_default() += 1;
+    }
+    let mut shared = 0;
+    for gram in &b {
+        if let Some(count) = counts.get_mut(gram.as_str()).filter(|count| **count > 0) {
+            *count -= 1;
+            shared += 1;
+        }
+    }
+    2.0 * shared as f64 / (a.len() + b.len()) as f64
+}
+
+/// Normalized Levenshtein similarity of two strings, counted in characters
+///
+/// Like `similarity_string`. The strings are compared after NFC normalization.
+pub fn similarity_levenshtein(a: &str, b: &str) -> f32 {
+    StringMeasure::default().similarity(a, b)
+}
+
+/// Normalized Damerau-Levenshtein similarity of two strings, counted in characters
+pub fn similarity_damerau_levenshtein(a: &str, b: &str) -> f32 {
+    StringMeasure {
+        metric: StringMetric::DamerauLevenshtein,
+        ..Default::default()
+    }
+    .similarity(a, b)
+}
+
+/// Jaro-Winkler similarity of two strings, counted in characters
+pub fn similarity_jaro_winkler(a: &str, b: &str) -> f32 {
+    StringMeasure {
+        metric: StringMetric::JaroWinkler,
+        ..Default::default()
+    }
+    .similarity(a, b)
+}
+
+/// Dice coefficient of the character n-grams of two strings
+///
+/// `n` is the number of consecutive characters per n-gram (2 for bigrams).
+pub fn similarity_ngram_dice(a: &str, b: &str, n: usize) -> f32 {
+    StringMeasure {
+        metric: StringMetric::NgramDice { n },
+        ..Default::default()
+    }
+    .similarity(a, b)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const METRICS: [StringMetric; 5] = [
+        StringMetric::Levenshtein,
+        StringMetric::DamerauLevenshtein,
+        StringMetric::JaroWinkler,
+        StringMetric::NgramDice { n: 2 },
+        StringMetric::NgramDice { n: 0 },
+    ];
+
+    fn measure(metric: StringMetric) -> StringMeasure {
+        StringMeasure {
+            metric,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn composed_and_decomposed_accents_are_equal() {
+        let (composed, decomposed) = ("caf\u{e9}", "cafe\u{301}");
+        for metric in METRICS {
+            assert_eq!(measure(metric).similarity(composed, decomposed), 1.0);
+            let raw = StringMeasure {
+                normalization: Normalization::None,
+                ..measure(metric)
+            };
+            assert!(raw.similarity(composed, decomposed) < 1.0, "{:?}", metric);
+        }
+        // NFKC also folds compatibility forms
+        let nfkc = StringMeasure {
+            normalization: Normalization::Nfkc,
+            ..Default::default()
+        };
+        assert_eq!(nfkc.similarity("\u{fb01}lm", "film"), 1.0);
+        assert!(StringMeasure::default().similarity("\u{fb01}lm", "film") < 1.0);
+    }
+
+    #[test]
+    fn multi_byte_characters_count_as_one_edit() {
+        // One substituted character out of three, whatever its byte length
+        assert!((similarity_levenshtein("東京都", "京京都") - 2.0 / 3.0).abs() < 1e-6);
+        assert!((similarity_levenshtein("abc", "xbc") - 2.0 / 3.0).abs() < 1e-6);
+        assert!((similarity_damerau_levenshtein("東京都", "京東都") - 2.0 / 3.0).abs() < 1e-6);
+
+        // A family emoji is several chars but a single grapheme
+        let (family, man) = ("a\u{1f468}\u{200d}\u{1f469}\u{200d}\u{1f467}", "a\u{1f468}");
+        let graphemes = StringMeasure {
+            unit: StringUnit::Graphemes,
+            ..Default::default()
+        };
+        assert_eq!(graphemes.similarity(family, man), 0.5);
+        assert!(similarity_levenshtein(family, man) < 0.5);
+    }
+
+    #[test]
+    fn case_folding_ignores_case() {
+        let folded = StringMeasure {
+            case_fold: true,
+            ..Default::default()
+        };
+        assert_eq!(folded.similarity("Amélie", "AMÉLIE"), 1.0);
+        assert_eq!(folded.similarity("STRASSE", "strasse"), 1.0);
+        assert!(StringMeasure::default().similarity("Amélie", "AMÉLIE") < 1.0);
+    }
+
+    #[test]
+    fn every_metric_stays_between_zero_and_one() {
+        let samples = [
+            "",
+            "a",
+            "ab",
+            "ba",
+            "abc",
+            "東京",
+            "café",
+            "🎬🎥",
+            "a b c d e f",
+        ];
+        for metric in METRICS {
+            for unit in [StringUnit::Chars, StringUnit::Graphemes] {
+                let measure = StringMeasure {
+                    unit,
+                    ..measure(metric)
+                };
+                for a in samples {
+                    for b in samples {
+                        let similarity = measure.similarity(a, b);
+                        assert!(
+                            (0.0..=1.0).contains(&similarity),
+                            "{:?} {} {}",
+                            metric,
+                            a,
+                            b
+                        );
+                        if a == b {
+                            assert_eq!(similarity, 1.0, "{:?} {}", metric, a);
+                        }
+                    }
+                }
+                assert_eq!(measure.similarity("", ""), 1.0);
+                assert!(measure.similarity("", "abc") < 1.0);
+            }
+        }
+    }
+}
@@ -0,0 +1,321 @@
+//! Taxonomy-based similarity for symbolic attributes such as genres
+//!
+//! Concepts are arranged in a tree. Every inner node carries the similarity
+//! of two different concepts below it, so the similarity of two concepts is
+//! the one of their closest common ancestor: "Action" and "Adventure" under
+//! an "Action & Adventure" node of 0.8 score 0.8, while concepts that only
+//! meet at the (implicit) root score 0.0. Pairs of concepts in different
+//! branches can be linked directly, turning the tree into a graph.
+//!
+//! A taxonomy is defined as a flat list of nodes, each naming its parent:
+//!
+//! ```toml
+//! [[nodes]]
+//! name = "Action & Adventure"
+//! similarity = 0.8
+//!
+//! [[nodes]]
+//! name = "Action"
+//! parent = "Action & Adventure"
+//!
+//! [[pairs]]
+//! a = "War"
+//! b = "History"
+//! similarity = 0.6
+//! ```
+//!
+//! Names are matched case insensitively. Concepts missing from the taxonomy
+//! are only similar to themselves.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// A concept of a taxonomy definition
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct TaxonomyNode {
+    /// Name of the concept (e.g. a genre name)
+    pub name: String,
+    /// Name of the parent concept, or None for a top-level concept
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parent: Option<String>,
+    /// Similarity of two different concepts below this one (0.0 to 1.0)
+    #[serde(default)]
+    pub similarity: f32,
+}
+
+/// A direct similarity between two concepts, overriding the tree
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct ConceptPair {
+    /// Name of the first concept
+    pub a: String,
+    /// Name of the second concept
+    pub b: String,
+    /// Similarity of the two concepts (0.0 to 1.0)
+    pub similarity: f32,
+}
+
+/// The serialized form of a taxonomy
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct TaxonomyDefinition {
+    /// Concepts of the tree, parents before or after their children
+    pub nodes: Vec<TaxonomyNode>,
+    /// Direct links between concepts of different branches
+    pub pairs: Vec<ConceptPair>,
+}
+
+/// A validated taxonomy, ready for similarity lookups
+///
+/// Built from a `TaxonomyDefinition` with `try_from`, which also happens when
+/// a taxonomy is deserialized.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+#[serde(try_from = "TaxonomyDefinition", into = "TaxonomyDefinition")]
+pub struct Taxonomy {
+    /// The resolved tree, shared by the genre measures holding the taxonomy
+    inner: Arc<TaxonomyTree>,
+}
+
+/// Nodes with resolved parents and pair links
+#[derive(Debug, Default, PartialEq)]
+struct TaxonomyTree {
+    definition: TaxonomyDefinition,
+    index: HashMap<String, usize>,
+    parents: Vec<Option<usize>>,
+    pairs: HashMap<(usize, usize), f32>,
+}
+
+impl TryFrom<TaxonomyDefinition> for Taxonomy {
+    type Error = String;
+
+    /// Checks the definition: unique names, known parents and pair concepts,
+    /// no cycles and similarities between 0.0 and 1.0
+    fn try_from(definition: TaxonomyDefinition) -> Result<Self, Self::Error> {
+        let check_similarity = |name: &str, similarity: f32| {
+            if (0.0..=1.0).contains(&similarity) {
+                Ok(())
+            } else {
+                Err(format!(
+                    "similarity of `{}` must be between 0.0 and 1.0, got {}",
+                    name, similarity
+                ))
+            }
+        };
+
+        let mut index = HashMap::new();
+        for (node_idx, node) in definition.nodes.iter().enumerate() {
+            check_similarity(&node.name, node.similarity)?;
+            if index.insert(node.name.to_lowercase(), node_idx).is_some() {
+                return Err(format!("duplicate taxonomy node `{}`", node.name));
+            }
+        }
+        let find = |name: &str| {
+            index
+                .get(&name.to_lowercase())
+                .copied()
+                .ok_or_else(|| format!("unknown taxonomy node `{}`", name))
+        };
+
+        let parents = definition
+            .nodes
+            .iter()
+            .map(|node| node.parent.as_deref().map(find).transpose())
+            .collect::<Result<Vec<_>, _>>()?;
+        for (node_idx, node) in definition.nodes.iter().enumerate() {
+            // A path to the root longer than the number of nodes is a cycle
+            let mut ancestor = parents[node_idx];
+            for _ in 0..definition.nodes.len() {
+                ancestor = ancestor.and_then(|ancestor| parents[ancestor]);
+            }
+            if ancestor.is_some() {
+                return Err(format!("taxonomy node `{}` is part of a cycle", node.name));
+            }
+        }
+
+        let mut pairs = HashMap::new();
+        for pair in &definition.pairs {
+            check_similarity(&format!("{} / {}", pair.a, pair.b), pair.similarity)?;
+            let (a, b) = (find(&pair.a)?, find(&pair.b)?);
+            pairs.insert((a.min(b), a.max(b)), pair.similarity);
+        }
+
+        Ok(Taxonomy {
+            inner: Arc::new(TaxonomyTree {
+                definition,
+                index,
+                parents,
+                pairs,
+            }),
+        })
+    }
+}
+
+impl From<Taxonomy> for TaxonomyDefinition {
+    fn from(taxonomy: Taxonomy) -> Self {
+        taxonomy.inner.definition.clone()
+    }
+}
+
+impl Taxonomy {
+    /// Returns the definition the taxonomy was built from
+    pub fn definition(&self) -> &TaxonomyDefinition {
+        &self.inner.definition
+    }
+
+    /// Returns true if the taxonomy has no concepts
+    pub fn is_empty(&self) -> bool {
+        self.inner.definition.nodes.is_empty()
+    }
+
+    /// Returns true if the concept is part of the taxonomy
+    pub fn contains(&self, name: &str) -> bool {
+        self.inner.index.contains_key(&name.to_lowercase())
+    }
+
+    /// Calculates the similarity of two concepts
+    ///
+    /// Parameters:
+    /// - a: Name of the first concept
+    /// - b: Name of the second concept
+    ///
+    /// Returns:
+    /// - 1.0 for the same concept, the similarity of a direct pair link if
+    ///   there is one, otherwise the similarity of the closest common ancestor
+    ///   (0.0 without a common ancestor or for unknown concepts)
+    pub fn similarity(&self, a: &str, b: &str) -> f32 {
+        let tree = &self.inner;
+        let (a, b) = (a.to_lowercase(), b.to_lowercase());
+        if a == b {
+            return 1.0;
+        }
+        let (Some(&a), Some(&b)) = (tree.index.get(&a), tree.index.get(&b)) else {
+            return 0.0;
+        };
+        if let Some(&similarity) = tree.pairs.get(&(a.min(b), a.max(b))) {
+            return similarity;
+        }
+
+        let ancestors_of_a: Vec<usize> = self.ancestors(a).collect();
+        self.ancestors(b)
+            .find(|ancestor| ancestors_of_a.contains(ancestor))
+            .map_or(0.0, |ancestor| tree.definition.nodes[ancestor].similarity)
+    }
+
+    /// The concept itself followed by its ancestors up to the top level
+    fn ancestors(&self, node_idx: usize) -> impl Iterator<Item = usize> + '_ {
+        std::iter::successors(Some(node_idx), |&node_idx| self.inner.parents[node_idx])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node(name: &str, parent: Option<&str>, similarity: f32) -> TaxonomyNode {
+        TaxonomyNode {
+            name: name.to_string(),
+            parent: parent.map(str::to_string),
+            similarity,
+        }
+    }
+
+    fn pair(a: &str, b: &str, similarity: f32) -> ConceptPair {
+        ConceptPair {
+            a: a.to_string(),
+            b: b.to_string(),
+            similarity,
+        }
+    }
+
+    /// Action and Adventure under a 0.8 node, Drama on its own
+    fn definition() -> TaxonomyDefinition {
+        TaxonomyDefinition {
+            nodes: vec![
+                node("Action & Adventure", None, 0.8),
+                node("Action", Some("Action & Adventure"), 0.0),
+                node("Adventure", Some("Action & Adventure"), 0.0),
+                node("Drama", None, 0.0),
+                node("War", None, 0.0),
+                node("History", None, 0.0),
+            ],
+            pairs: vec![pair("War", "History", 0.6)],
+        }
+    }
+
+    fn rejection(definition: TaxonomyDefinition) -> String {
+        Taxonomy::try_from(definition).unwrap_err()
+    }
+
+    #[test]
+    fn similarity_is_the_one_of_the_closest_common_ancestor() {
+        let taxonomy = Taxonomy::try_from(definition()).unwrap();
+        assert_eq!(taxonomy.similarity("Action", "Adventure"), 0.8);
+        assert_eq!(taxonomy.similarity("adventure", "ACTION"), 0.8);
+        assert_eq!(taxonomy.similarity("Action", "Action & Adventure"), 0.8);
+        assert_eq!(taxonomy.similarity("War", "History"), 0.6);
+        assert_eq!(taxonomy.similarity("History", "War"), 0.6);
+        assert_eq!(taxonomy.similarity("Drama", "Drama"), 1.0);
+    }
+
+    #[test]
+    fn unrelated_and_unknown_concepts_score_zero() {
+        let taxonomy = Taxonomy::try_from(definition()).unwrap();
+        assert_eq!(taxonomy.similarity("Action", "Drama"), 0.0);
+        assert_eq!(taxonomy.similarity("Action", "Western"), 0.0);
+        assert_eq!(taxonomy.similarity("Western", "western"), 1.0);
+        assert!(!taxonomy.contains("Western"));
+    }
+
+    #[test]
+    fn cycles_are_rejected() {
+        let mut definition = definition();
+        definition.nodes[0].parent = Some("Adventure".to_string());
+        assert!(rejection(definition).contains("cycle"));
+    }
+
+    #[test]
+    fn duplicate_nodes_are_rejected() {
+        let mut definition = definition();
+        definition.nodes.push(node("drama", None, 0.0));
+        assert!(rejection(definition).contains("duplicate"));
+    }
+
+    #[test]
+    fn unknown_parents_are_rejected() {
+        let mut definition = definition();
+        definition
+            .nodes
+            .push(node("Western", Some("Frontier"), 0.0));
+        assert!(rejection(definition).contains("`Frontier`"));
+    }
+
+    #[test]
+    fn unknown_pair_concepts_are_rejected() {
+        let mut definition = definition();
+        definition.pairs.push(pair("War", "Western", 0.5));
+        assert!(rejection(definition).contains("`Western`"));
+    }
+
+    #[test]
+    fn similarities_outside_zero_and_one_are_rejected() {
+        let mut definition = definition();
+        definition.nodes[0].similarity = 1.5;
+        assert!(rejection(definition).contains("between 0.0 and 1.0"));
+
+        let mut definition = self::definition();
+        definition.pairs[0].similarity = -0.1;
+        assert!(rejection(definition).contains("between 0.0 and 1.0"));
+
+        let mut definition = self::definition();
+        definition.nodes[3].similarity = f32::NAN;
+        assert!(rejection(definition).contains("between 0.0 and 1.0"));
+    }
+
+    #[test]
+    fn default_genre_taxonomy_is_valid() {
+        let taxonomy: Taxonomy =
+            toml::from_str(include_str!("../../data/genre_taxonomy.toml")).unwrap();
+        assert!(!taxonomy.is_empty());
+        assert!(taxonomy.similarity("Action", "Adventure") > 0.0);
+    }
+}
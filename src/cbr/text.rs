@@ -0,0 +1,403 @@
+//! Text similarity for free-text attributes such as plot overviews
+//!
+//! Texts are tokenised into lowercase words, stop words are removed and each
+//! text becomes a TF-IDF vector. A TfIdf model is fitted once over all texts
+//! of the case base; two texts are then compared with the cosine similarity
+//! of their vectors.
+
+use std::collections::HashMap;
+
+/// Common English words that carry no meaning on their own
+pub const STOP_WORDS: &[&str] = &[
+    "a",
+    "about",
+    "above",
+    "after",
+    "again",
+    "against",
+    "all",
+    "also",
+    "am",
+    "an",
+    "and",
+    "any",
+    "are",
+    "as",
+    "at",
+    "be",
+    "because",
+    "been",
+    "before",
+    "being",
+    "below",
+    "between",
+    "both",
+    "but",
+    "by",
+    "can",
+    "could",
+    "did",
+    "do",
+    "does",
+    "doing",
+    "down",
+    "during",
+    "each",
+    "even",
+    "ever",
+    "few",
+    "for",
+    "from",
+    "further",
+    "get",
+    "gets",
+    "had",
+    "has",
+    "have",
+    "having",
+    "he",
+    "her",
+    "here",
+    "hers",
+    "herself",
+    "him",
+    "himself",
+    "his",
+    "how",
+    "however",
+    "i",
+    "if",
+    "in",
+    "into",
+    "is",
+    "it",
+    "its",
+    "itself",
+    "just",
+    "may",
+    "me",
+    "more",
+    "most",
+    "much",
+    "must",
+    "my",
+    "myself",
+    "never",
+    "no",
+    "nor",
+    "not",
+    "now",
+    "of",
+    "off",
+    "on",
+    "once",
+    "one",
+    "only",
+    "or",
+    "other",
+    "our",
+    "ours",
+    "ourselves",
+    "out",
+    "over",
+    "own",
+    "same",
+    "she",
+    "should",
+    "so",
+    "some",
+    "such",
+    "than",
+    "that",
+    "the",
+    "their",
+    "theirs",
+    "them",
+    "themselves",
+    "then",
+    "there",
+    "these",
+    "they",
+    "this",
+    "those",
+    "through",
+    "to",
+    "too",
+    "under",
+    "until",
+    "up",
+    "upon",
+    "very",
+    "was",
+    "we",
+    "were",
+    "what",
+    "when",
+    "where",
+    "which",
+    "while",
+    "who",
+    "whom",
+    "whose",
+    "why",
+    "will",
+    "with",
+    "within",
+    "without",
+    "would",
+    "you",
+    "your",
+    "yours",
+    "yourself",
+    "yourselves",
+];
+
+/// Splits a text into lowercase word tokens, removing stop words
+///
+/// Parameters:
+/// - text: The text to tokenise
+///
+/// Returns:
+/// - The tokens in text order. Words are maximal runs of alphanumeric
+///   characters; single-character words and stop words are dropped.
+pub fn tokenize(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| word.chars().nth(1).is_some())
+        .map(str::to_lowercase)
+        .filter(|word| !STOP_WORDS.contains(&word.as_str()))
+        .collect()
+}
+
+/// A sparse TF-IDF vector with unit length
+///
+/// Terms are stored as `(term id, weight)` pairs sorted by term id, so the dot
+/// product of two vectors is a single merge pass.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TermVector {
+    terms: Vec<(u32, f32)>,
+}
+
+impl TermVector {
+    /// Returns true if the text had no known terms
+    pub fn is_empty(&self) -> bool {
+        self.terms.is_empty()
+    }
+
+    /// Returns the `(term id, weight)` pairs, sorted by term id
+    pub fn terms(&self) -> &[(u32, f32)] {
+        &self.terms
+    }
+
+    /// Builds the normalized mean of several vectors
+    ///
+    /// The result points in the average direction of the inputs, so it is
+    /// similar to texts that share terms with any of them. Empty vectors are
+    /// ignored.
+    pub fn centroid<'a>(vectors: impl IntoIterator<Item = &'a TermVector>) -> TermVector {
+        let mut sums: HashMap<u32, f32> = HashMap::new();
+        for vector in vectors {
+            for &(term_id, weight) in &vector.terms {
+                *sums.entry(term_id).or_default() += weight;
+            }
+        }
+        TermVector::normalized(sums.into_iter().collect())
+    }
+
+    /// Sorts the terms by id and scales the weights to unit length
+    fn normalized(mut terms: Vec<(u32, f32)>) -> TermVector {
+        terms.sort_unstable_by_key(|&(term_id, _)| term_id);
+
+        let norm = terms
+            .iter()
+            .map(|(_, weight)| weight * weight)
+            .sum::<f32>()
+            .sqrt();
+        if norm > 0.0 {
+            for (_, weight) in &mut terms {
+                *weight /= norm;
+            }
+        }
+        TermVector { terms }
+    }
+}
+
+/// Calculates the cosine similarity between two TF-IDF vectors
+///
+/// Parameters:
+/// - a: First vector
+/// - b: Second vector
+///
+/// Returns:
+/// - A float value between 0.0 and 1.0, where:
+///   - 1.0 means the texts use the same terms in the same proportions
+///   - 0.0 means the texts share no terms (or one of them is empty)
+///
+/// Both vectors have unit length, so the cosine is their dot product.
+pub fn similarity_cosine(a: &TermVector, b: &TermVector) -> f32 {
+    let (mut i, mut j) = (0, 0);
+    let mut dot = 0.0;
+    while i < a.terms.len() && j < b.terms.len() {
+        let (term_a, weight_a) = a.terms[i];
+        let (term_b, weight_b) = b.terms[j];
+        match term_a.cmp(&term_b) {
+            std::cmp::Ordering::Less => i += 1,
+            std::cmp::Ordering::Greater => j += 1,
+            std::cmp::Ordering::Equal => {
+                dot += weight_a * weight_b;
+                i += 1;
+                j += 1;
+            }
+        }
+    }
+    dot.clamp(0.0, 1.0)
+}
+
+/// A TF-IDF model: the vocabulary of a collection of texts and the inverse
+/// document frequency of each term
+#[derive(Debug, Clone, Default)]
+pub struct TfIdf {
+    /// Term -> term id
+    vocabulary: HashMap<String, u32>,
+    /// Inverse document frequency, indexed by term id
+    idf: Vec<f32>,
+}
+
+impl TfIdf {
+    /// Fits the model on a collection of texts
+    ///
+    /// Parameters:
+    /// - documents: All texts of the case base
+    ///
+    /// Returns:
+    /// - The model, with a smoothed IDF of `ln((1 + n) / (1 + df)) + 1` for a term
+    ///   appearing in `df` of the `n` texts. Rare terms get a higher weight.
+    pub fn fit<'a>(documents: impl IntoIterator<Item = &'a str>) -> Self {
+        let mut vocabulary: HashMap<String, u32> = HashMap::new();
+        let mut document_frequency: Vec<u32> = Vec::new();
+        let mut documents_count = 0;
+
+        for document in documents {
+            documents_count += 1;
+            let mut tokens = tokenize(document);
+            tokens.sort_unstable();
+            tokens.dedup();
+            for token in tokens {
+                let next_id = vocabulary.len() as u32;
+                let term_id = *vocabulary.entry(token).or_insert(next_id);
+                if term_id == next_id {
+                    document_frequency.push(0);
+                }
+                document_frequency[term_id as usize] += 1;
+            }
+        }
+
+        let n = documents_count as f32;
+        let idf = document_frequency
+            .into_iter()
+            .map(|df| ((1.0 + n) / (1.0 + df as f32)).ln() + 1.0)
+            .collect();
+        TfIdf { vocabulary, idf }
+    }
+
+    /// Converts a text into a unit-length TF-IDF vector
+    ///
+    /// Parameters:
+    /// - text: The text to convert
+    ///
+    /// Returns:
+    /// - The vector of term frequency times IDF, normalized to unit length.
+    ///   Terms outside the vocabulary are ignored; a text without known terms
+    ///   gives an empty vector.
+    pub fn vectorize(&self, text: &str) -> TermVector {
+        let mut counts: HashMap<u32, u32> = HashMap::new();
+        for token in tokenize(text) {
+            if let Some(&term_id) = self.vocabulary.get(&token) {
+                *counts.entry(term_id).or_default() += 1;
+            }
+        }
+
+        TermVector::normalized(
+            counts
+                .into_iter()
+                .map(|(term_id, count)| (term_id, count as f32 * self.idf[term_id as usize]))
+                .collect(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OVERVIEWS: &[&str] = &[
+        "Batman fights the Joker in Gotham City.",
+        "Batman and Robin protect Gotham from Bane.",
+        "A young wizard attends a school of magic.",
+        "Two friends find a wizard's wand in the city.",
+    ];
+
+    #[test]
+    fn tokens_are_lowercase_words_without_stop_words() {
+        assert_eq!(
+            tokenize("The Dark Knight's rise, in 2012!"),
+            ["dark", "knight", "rise", "2012"]
+        );
+        assert_eq!(tokenize("Amélie à Paris"), ["amélie", "paris"]);
+        assert!(tokenize("It is what it is.").is_empty());
+        assert!(tokenize("").is_empty());
+    }
+
+    #[test]
+    fn rare_terms_weigh_more() {
+        let model = TfIdf::fit(OVERVIEWS.iter().copied());
+        let idf = |term: &str| model.idf[model.vocabulary[term] as usize];
+
+        // "batman" is in two of the four texts, "joker" only in one
+        assert!(idf("joker") > idf("batman"));
+        assert!((idf("batman") - ((5.0_f32 / 3.0).ln() + 1.0)).abs() < 1e-6);
+
+        let vector = model.vectorize("batman joker");
+        let weight = |term: &str| {
+            let term_id = model.vocabulary[term];
+            vector
+                .terms()
+                .iter()
+                .find(|&&(id, _)| id == term_id)
+                .map(|&(_, weight)| weight)
+                .unwrap()
+        };
+        assert!(weight("joker") > weight("batman"));
+        assert!(model.vectorize("unheard words").is_empty());
+    }
+
+    #[test]
+    fn cosine_stays_between_0_and_1() {
+        let model = TfIdf::fit(OVERVIEWS.iter().copied());
+        let vectors: Vec<TermVector> = OVERVIEWS
+            .iter()
+            .chain(&["", "gotham gotham gotham"])
+            .map(|text| model.vectorize(text))
+            .collect();
+        for a in &vectors {
+            for b in &vectors {
+                let similarity = similarity_cosine(a, b);
+                assert!((0.0..=1.0).contains(&similarity), "{}", similarity);
+                assert_eq!(similarity, similarity_cosine(b, a));
+            }
+        }
+
+        let (batman, wizard) = (&vectors[0], &vectors[2]);
+        assert!(similarity_cosine(batman, &vectors[1]) > 0.0);
+        assert_eq!(similarity_cosine(batman, wizard), 0.0);
+        assert_eq!(similarity_cosine(batman, &TermVector::default()), 0.0);
+    }
+
+    #[test]
+    fn identical_texts_are_fully_similar() {
+        let model = TfIdf::fit(OVERVIEWS.iter().copied());
+        for text in OVERVIEWS {
+            let similarity = similarity_cosine(&model.vectorize(text), &model.vectorize(text));
+            assert!((similarity - 1.0).abs() < 1e-6, "{}: {}", text, similarity);
+        }
+    }
+}
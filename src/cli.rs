@@ -0,0 +1,373 @@
+use clap::{Parser, Subcommand, ValueEnum};
+use movie_cbr::case_base::{Aggregation, MovieCaseBase, MovieQuery};
+use movie_cbr::movie::{
+    Bounds, Company, Genre, Keyword, Movie, QueryCase, SimilarityMeasures, SimilarityWeights,
+};
+use serde::Serialize;
+use std::io::{self, Write};
+use std::path::PathBuf;
+
+/// Default location of the TMDB movies dataset
+pub const DEFAULT_DATA_PATH: &str = "./data/tmdb_5000_movies.csv";
+
+/// File name of the TMDB credits dataset, looked up next to the movies file
+const DEFAULT_CREDITS_FILE: &str = "tmdb_5000_credits.csv";
+
+/// Command-line arguments of the movie_cbr binary
+///
+/// Without a subcommand the GUI is launched.
+#[derive(Parser, Debug)]
+#[command(name = "movie_cbr", about = "Find movies similar to your favorites")]
+pub struct Cli {
+    /// Path to the TMDB movies CSV file
+    #[arg(long, global = true, default_value = DEFAULT_DATA_PATH)]
+    pub data: PathBuf,
+    /// Path to the TMDB credits CSV file (cast and crew)
+    ///
+    /// Defaults to tmdb_5000_credits.csv next to the movies file, if it exists.
+    #[arg(long, global = true)]
+    pub credits: Option<PathBuf>,
+    /// Optional TOML/JSON file with similarity weights
+    #[arg(long, global = true)]
+    pub weights: Option<PathBuf>,
+    /// Optional TOML/JSON file with local similarity measures
+    #[arg(long, global = true)]
+    pub measures: Option<PathBuf>,
+    /// Optional TOML/JSON genre taxonomy replacing data/genre_taxonomy.toml
+    #[arg(long, global = true)]
+    pub taxonomy: Option<PathBuf>,
+    /// Skip malformed CSV rows instead of aborting, reporting them on stderr
+    #[arg(long, global = true)]
+    pub lenient: bool,
+    /// Headless command to run instead of the GUI
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+/// Headless commands
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Print the movies most similar to one or more given titles
+    Similar {
+        /// Title of a reference movie (case insensitive); repeat for "more like these"
+        #[arg(long, required = true)]
+        title: Vec<String>,
+        /// How similarities to several reference movies are combined: mean, min, max or centroid
+        #[arg(long, default_value_t = Aggregation::Mean)]
+        aggregate: Aggregation,
+        /// Title of a disliked movie: similar movies are ranked lower; repeatable
+        #[arg(long = "not")]
+        not: Vec<String>,
+        /// How strongly resembling a disliked movie lowers the score
+        #[arg(long, default_value_t = MovieQuery::default().dislike_penalty)]
+        penalty: f32,
+        /// Show at most one movie of each franchise (e.g. only the best matching sequel)
+        #[arg(long)]
+        one_per_franchise: bool,
+        /// Leave out the other movies of the reference movies' franchises
+        #[arg(long)]
+        hide_franchise: bool,
+        /// Number of similar movies to print
+        #[arg(long, default_value_t = 10)]
+        top: usize,
+        /// Output format
+        #[arg(long, value_enum, default_value_t = Format::Table)]
+        format: Format,
+        /// Score every movie instead of only those sharing a genre, keyword, company,
+        /// director or top-billed actor
+        #[arg(long)]
+        exhaustive: bool,
+    },
+    /// Print the movies best matching a description, compared only on the given attributes
+    Describe {
+        /// Wanted genre (by name, case insensitive); repeatable
+        #[arg(long)]
+        genre: Vec<String>,
+        /// Wanted keyword (by name, case insensitive); repeatable
+        #[arg(long)]
+        keyword: Vec<String>,
+        /// Wanted production company (by name, case insensitive); repeatable
+        #[arg(long)]
+        company: Vec<String>,
+        /// Lowest wanted budget
+        #[arg(long)]
+        min_budget: Option<u32>,
+        /// Highest wanted budget
+        #[arg(long)]
+        max_budget: Option<u32>,
+        /// First wanted release year
+        #[arg(long)]
+        from_year: Option<i32>,
+        /// Last wanted release year
+        #[arg(long)]
+        to_year: Option<i32>,
+        /// Fragment of the title (case insensitive)
+        #[arg(long)]
+        title: Option<String>,
+        /// Number of movies to print
+        #[arg(long, default_value_t = 10)]
+        top: usize,
+        /// Output format
+        #[arg(long, value_enum, default_value_t = Format::Table)]
+        format: Format,
+    },
+}
+
+/// Output formats of the `similar` and `describe` commands
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum Format {
+    /// Human-readable aligned columns
+    Table,
+    /// JSON array of recommendations
+    Json,
+    /// CSV with a header row
+    Csv,
+}
+
+/// A single ranked recommendation, as printed by the `similar` command
+#[derive(Serialize, Debug)]
+struct Recommendation<'a> {
+    /// Rank of the movie, starting at 1
+    rank: usize,
+    /// TMDB id of the movie
+    id: u32,
+    /// Title of the movie
+    title: &'a str,
+    /// Similarity to the reference movies, lowered for resembling disliked ones (0.0 to 1.0)
+    similarity: f32,
+}
+
+/// Loads the similarity weights given on the command line, or the defaults
+pub fn load_weights(cli: &Cli) -> Result<SimilarityWeights, Box<dyn std::error::Error>> {
+    match &cli.weights {
+        Some(path) => SimilarityWeights::from_path(path),
+        None => Ok(SimilarityWeights::default()),
+    }
+}
+
+/// Loads the similarity measures and genre taxonomy given on the command line, or the defaults
+pub fn load_measures(cli: &Cli) -> Result<SimilarityMeasures, Box<dyn std::error::Error>> {
+    let mut measures = match &cli.measures {
+        Some(path) => SimilarityMeasures::from_path(path)?,
+        None => SimilarityMeasures::default(),
+    };
+    if let Some(path) = &cli.taxonomy {
+        measures.genres.load_taxonomy(path)?;
+    }
+    Ok(measures)
+}
+
+/// Returns the credits CSV to join: the one given on the command line, or
+/// tmdb_5000_credits.csv next to the movies file if it exists
+pub fn credits_path(cli: &Cli) -> Option<PathBuf> {
+    cli.credits.clone().or_else(|| {
+        let path = cli.data.with_file_name(DEFAULT_CREDITS_FILE);
+        path.exists().then_some(path)
+    })
+}
+
+/// Loads the case base from the CSVs given on the command line
+///
+/// In lenient mode, malformed rows are skipped and reported on stderr.
+fn load_case_base(cli: &Cli) -> Result<MovieCaseBase, Box<dyn std::error::Error>> {
+    if !cli.lenient {
+        let mut case_base = MovieCaseBase::load(&cli.data)?;
+        if let Some(path) = credits_path(cli) {
+            case_base.load_credits(&path)?;
+        }
+        return Ok(case_base);
+    }
+
+    let (mut case_base, mut report) = MovieCaseBase::load_lenient(&cli.data)?;
+    if let Some(path) = credits_path(cli) {
+        case_base.load_credits_lenient(&path, &mut report)?;
+    }
+    if !report.is_clean() {
+        eprintln!("{}", report.summary());
+        for row in report.rejected_rows() {
+            eprintln!("  {}", row);
+        }
+    }
+    Ok(case_base)
+}
+
+/// Runs a headless command and prints its results to stdout
+///
+/// # Arguments
+/// * `cli` - The parsed command-line arguments
+/// * `command` - The command to run
+///
+/// # Returns
+/// * `Result<(), Box<dyn std::error::Error>>` - Success or error
+pub fn run(cli: &Cli, command: &Command) -> Result<(), Box<dyn std::error::Error>> {
+    match command {
+        Command::Similar {
+            title,
+            aggregate,
+            not,
+            penalty,
+            one_per_franchise,
+            hide_franchise,
+            top,
+            format,
+            exhaustive,
+        } => {
+            let mut case_base = load_case_base(cli)?;
+            case_base.set_weights(load_weights(cli)?);
+            case_base.set_measures(load_measures(cli)?);
+            case_base.set_exhaustive(*exhaustive);
+            let movies = case_base.movies();
+
+            // Find the liked and disliked movies by title
+            let find_all = |titles: &[String]| {
+                titles
+                    .iter()
+                    .map(|title| {
+                        case_base
+                            .find_by_title(title)
+                            .ok_or_else(|| format!("movie not found: {}", title))
+                    })
+                    .collect::<Result<Vec<usize>, String>>()
+            };
+            let query = MovieQuery {
+                liked: find_all(title)?,
+                disliked: find_all(not)?,
+                aggregation: *aggregate,
+                dislike_penalty: *penalty,
+                one_per_franchise: *one_per_franchise,
+                hide_query_franchise: *hide_franchise,
+            };
+
+            // Retrieve the best matches (the liked and disliked movies are excluded)
+            let retrieved = case_base
+                .retrieve(&query, *top)
+                .ok_or("the query refers to a movie outside the case base")?;
+            let recommendations = to_recommendations(movies, retrieved);
+            print_recommendations(&recommendations, *format)
+        }
+        Command::Describe {
+            genre,
+            keyword,
+            company,
+            min_budget,
+            max_budget,
+            from_year,
+            to_year,
+            title,
+            top,
+            format,
+        } => {
+            let mut case_base = load_case_base(cli)?;
+            case_base.set_weights(load_weights(cli)?);
+            case_base.set_measures(load_measures(cli)?);
+
+            // Build the query case from the known vocabularies
+            let query = QueryCase {
+                genres: find_named(case_base.genres(), genre, Genre::name, "genre")?,
+                keywords: find_named(case_base.keywords(), keyword, Keyword::name, "keyword")?,
+                production_companies: find_named(
+                    case_base.companies(),
+                    company,
+                    Company::name,
+                    "company",
+                )?,
+                budget: Bounds {
+                    min: *min_budget,
+                    max: *max_budget,
+                },
+                years: Bounds {
+                    min: *from_year,
+                    max: *to_year,
+                },
+                title: title.clone(),
+            };
+            if query.is_empty() {
+                return Err("describe needs at least one attribute".into());
+            }
+
+            let recommendations = to_recommendations(
+                case_base.movies(),
+                case_base.retrieve_query_case(&query, *top),
+            );
+            print_recommendations(&recommendations, *format)
+        }
+    }
+}
+
+/// Looks up vocabulary items by name (case insensitive)
+fn find_named<T: Clone>(
+    items: &[T],
+    names: &[String],
+    name: fn(&T) -> &str,
+    kind: &str,
+) -> Result<Vec<T>, String> {
+    names
+        .iter()
+        .map(|wanted| {
+            items
+                .iter()
+                .find(|item| name(item).eq_ignore_ascii_case(wanted))
+                .cloned()
+                .ok_or_else(|| format!("{} not found: {}", kind, wanted))
+        })
+        .collect()
+}
+
+/// Ranks retrieval results as recommendations
+fn to_recommendations(movies: &[Movie], results: Vec<(usize, f32)>) -> Vec<Recommendation<'_>> {
+    results
+        .into_iter()
+        .enumerate()
+        .map(|(rank, (idx, similarity))| Recommendation {
+            rank: rank + 1,
+            id: movies[idx].id,
+            title: &movies[idx].title,
+            similarity,
+        })
+        .collect()
+}
+
+/// Prints recommendations to stdout in the requested format
+fn print_recommendations(
+    recommendations: &[Recommendation],
+    format: Format,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut stdout = io::stdout().lock();
+    match format {
+        Format::Table => {
+            let title_width = recommendations
+                .iter()
+                .map(|recommendation| recommendation.title.chars().count())
+                .max()
+                .unwrap_or(0)
+                .max("Title".len());
+            writeln!(
+                stdout,
+                "{:>4}  {:>8}  {:<title_width$}  {:>10}",
+                "Rank", "Id", "Title", "Similarity"
+            )?;
+            for recommendation in recommendations {
+                writeln!(
+                    stdout,
+                    "{:>4}  {:>8}  {:<title_width$}  {:>9.1}%",
+                    recommendation.rank,
+                    recommendation.id,
+                    recommendation.title,
+                    recommendation.similarity * 100.0
+                )?;
+            }
+        }
+        Format::Json => {
+            serde_json::to_writer_pretty(&mut stdout, recommendations)?;
+            writeln!(stdout)?;
+        }
+        Format::Csv => {
+            let mut writer = csv::Writer::from_writer(stdout);
+            for recommendation in recommendations {
+                writer.serialize(recommendation)?;
+            }
+            writer.flush()?;
+        }
+    }
+    Ok(())
+}
@@ -1,8 +1,15 @@
 // Import necessary modules and types from the crate and external dependencies
-use crate::movie::Movie; // Import the Movie struct from the local movie module
+use crate::case_base::{Aggregation, LoadReport, MovieCaseBase, MovieQuery}; // Import the case base, retrieval engine and load report
+use crate::cbr::HasId; // Import the ID trait to compare picked genres, keywords and companies
+use crate::cbr::numeric::NumericScale; // Import the scales numeric attributes can be compared on
+// Import the Movie struct, similarity weights and measures from the local movie module
+use crate::movie::{
+    Company, GenreMetric, Keyword, Movie, QueryCase, SetMetric, SimilarityExplanation,
+    SimilarityMeasures, SimilarityWeights,
+};
 use eframe::egui::{self, CursorIcon, Margin}; // Import egui and related components for GUI
 use egui::{Color32, CornerRadius, RichText, Stroke, Vec2}; // Import specific egui types for styling
-use std::{fs::File, path::Path}; // Import File and Path from standard library for file operations
+use std::path::Path; // Import Path from standard library for file operations
 
 /// ColorTheme defines the color palette used throughout the application
 /// This struct centralizes all color definitions for consistent UI styling
@@ -41,18 +48,86 @@ impl Default for ColorTheme {
 // Constant defining how many similar movies to display
 const TOP_N: usize = 10;
 
+// One optional end of a range (budget, release years): a checkbox enabling
+// it and a drag value editing it, showing `initial` while it is off
+fn optional_bound<T: Copy>(
+    ui: &mut egui::Ui,
+    label: &str,
+    bound: &mut Option<T>,
+    initial: T,
+    drag_value: impl FnOnce(&mut T) -> egui::DragValue<'_>,
+) {
+    let mut enabled = bound.is_some();
+    let mut value = bound.unwrap_or(initial);
+    ui.checkbox(&mut enabled, label);
+    ui.add_enabled(enabled, drag_value(&mut value));
+    *bound = enabled.then_some(value);
+}
+
+// Picker for a set-valued attribute with a large vocabulary (keywords, companies):
+// the picked items are shown as removable buttons, and typing in the filter
+// lists matching items to add
+fn pick_items<T: HasId + Clone>(
+    ui: &mut egui::Ui,
+    label: &str,
+    filter: &mut String,
+    vocabulary: &[T],
+    picked: &mut Vec<T>,
+    name: fn(&T) -> &str,
+) {
+    ui.horizontal(|ui| {
+        ui.label(label);
+        ui.text_edit_singleline(filter);
+    });
+    ui.horizontal_wrapped(|ui| {
+        picked.retain(|item| !ui.small_button(format!("{} ✖", name(item))).clicked());
+
+        let query = filter.trim().to_lowercase();
+        if query.is_empty() {
+            return;
+        }
+        let matches: Vec<&T> = vocabulary
+            .iter()
+            .filter(|item| name(item).to_lowercase().contains(&query))
+            .filter(|item| !picked.iter().any(|picked| picked.id() == item.id()))
+            .take(8)
+            .collect();
+        for item in matches {
+            if ui.small_button(format!("+ {}", name(item))).clicked() {
+                picked.push(item.clone());
+            }
+        }
+    });
+}
+
+// Human-readable name of a numeric scale, shown in the scale selectors
+fn scale_label(scale: NumericScale) -> &'static str {
+    match scale {
+        NumericScale::Linear => "Linear",
+        NumericScale::Log => "Logarithmic",
+        NumericScale::PercentileRank => "Percentile rank",
+    }
+}
+
 /// Main application struct for the Movie Similarity App
 /// Contains all state needed to run the application
 #[derive(Default)]
 pub struct MovieSimilarityApp {
-    movies: Vec<Movie>,                  // List of all movies loaded from CSV
+    case_base: MovieCaseBase,            // Movies loaded from CSV and the retrieval engine
     selected_movie_index: Option<usize>, // Currently selected movie (if any)
+    query: MovieQuery,                   // Liked (selected) and disliked movies of the query
+    query_case: QueryCase,               // Description of the wanted movie ("Describe a movie")
+    describing: bool,                    // Whether the results match the description
+    keyword_filter: String,              // Search text of the keyword picker
+    company_filter: String,              // Search text of the company picker
     similar_movies: Vec<(usize, f32)>,   // List of similar movies with similarity scores
-    min_budget: u32,                     // Minimum movie budget (used for normalization)
-    max_budget: u32,                     // Maximum movie budget (used for normalization)
     search_query: String,                // Current search query text
     filtered_indices: Vec<usize>,        // Indices of movies matching the search query
     pending_selection: Option<usize>,    // Movie selection that hasn't been processed yet
+    pending_toggle: Option<usize>,       // Movie added to/removed from the query (Ctrl-click)
+    pending_dislike: Option<usize>,      // Movie given a thumbs-down that hasn't been processed yet
+    load_report: LoadReport,             // Rows skipped while loading the CSV
+    load_error: Option<String>,          // Error that prevented loading the CSV, if any
     theme: ColorTheme,                   // Color theme for the application
 }
 
@@ -65,42 +140,26 @@ impl MovieSimilarityApp {
     /// # Returns
     /// * `Result<(), Box<dyn std::error::Error>>` - Success or error
     ///
-    /// This function reads the CSV, deserializes it to Movie objects,
-    /// initializes the min/max budget values for later normalization,
-    /// and sets up the filtered indices list.
+    /// This function loads the case base (keeping the current weights)
+    /// and sets up the filtered indices list. Malformed rows are skipped and
+    /// summarised in a warning banner; if the file cannot be read at all,
+    /// the error is shown in the window.
     pub fn load_movies(&mut self, path: &Path) -> Result<(), Box<dyn std::error::Error>> {
-        // Open the file at the specified path
-        let file = File::open(path)?;
-
-        // Create a CSV reader with headers and flexible parsing
-        let mut csv_reader = csv::ReaderBuilder::new()
-            .has_headers(true)
-            .flexible(true)
-            .from_reader(file);
-
-        // Deserialize the CSV rows into Movie objects
-        self.movies = csv_reader
-            .deserialize()
-            .collect::<Result<Vec<Movie>, _>>()?;
-
-        // Find minimum budget across all movies (for normalization)
-        self.min_budget = self
-            .movies
-            .iter()
-            .map(|movie| movie.budget)
-            .min()
-            .unwrap_or(0);
-
-        // Find maximum budget across all movies (for normalization)
-        self.max_budget = self
-            .movies
-            .iter()
-            .map(|movie| movie.budget)
-            .max()
-            .unwrap_or(0);
+        let weights = self.case_base.weights().clone();
+        let (case_base, load_report) = match MovieCaseBase::load_lenient(path) {
+            Ok(loaded) => loaded,
+            Err(err) => {
+                self.load_error = Some(format!("Error loading {}: {}", path.display(), err));
+                return Err(err);
+            }
+        };
+        self.case_base = case_base;
+        self.case_base.set_weights(weights);
+        self.load_report = load_report;
+        self.load_error = None;
 
         // Initialize filtered_indices with all movie indices
-        self.filtered_indices = (0..self.movies.len()).collect();
+        self.filtered_indices = (0..self.case_base.len()).collect();
 
         // Initialize the color theme
         self.theme = ColorTheme::default();
@@ -108,50 +167,75 @@ impl MovieSimilarityApp {
         Ok(())
     }
 
-    /// Calculates similarity scores between the selected movie and all other movies
+    /// Replaces the local measures of configurable attributes
+    ///
+    /// # Arguments
+    /// * `measures` - The new measures (e.g. loaded with `SimilarityMeasures::from_path`)
+    ///
+    /// Similarities for the currently selected movie are recalculated immediately.
+    pub fn set_measures(&mut self, measures: SimilarityMeasures) {
+        self.case_base.set_measures(measures);
+        self.calculate_similarities();
+    }
+
+    /// Joins cast and crew from a TMDB credits CSV file to the loaded movies
+    ///
+    /// # Arguments
+    /// * `path` - Path to the CSV file containing the credits
+    ///
+    /// # Returns
+    /// * `Result<(), Box<dyn std::error::Error>>` - Success or error
+    ///
+    /// Malformed rows are skipped and added to the warning banner.
+    pub fn load_credits(&mut self, path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+        self.case_base
+            .load_credits_lenient(path, &mut self.load_report)?;
+        self.calculate_similarities();
+        Ok(())
+    }
+
+    /// Replaces the attribute weights used for similarity calculation
+    ///
+    /// # Arguments
+    /// * `weights` - The new weights (e.g. loaded with `SimilarityWeights::from_path`)
+    ///
+    /// Similarities for the currently selected movie are recalculated immediately.
+    pub fn set_weights(&mut self, weights: SimilarityWeights) {
+        self.case_base.set_weights(weights);
+        self.calculate_similarities();
+    }
+
+    /// Retrieves the movies most similar to the selected movies
     ///
-    /// This function is called when a movie is selected. It:
-    /// 1. Gets the selected movie as the reference
-    /// 2. Calculates similarity for each movie against the reference
-    /// 3. Sorts the results by similarity score in descending order
+    /// This function is called when the selection, the weights or the
+    /// aggregation change. Without a selected movie, the movies matching the
+    /// description are retrieved instead. The retrieval itself is delegated
+    /// to the case base.
     fn calculate_similarities(&mut self) {
-        if let Some(selected_idx) = self.selected_movie_index {
-            let reference_movie = &self.movies[selected_idx];
-            let min_budget = self.min_budget;
-            let max_budget = self.max_budget;
-
-            // Calculate similarity scores for all movies compared to the reference
-            let similar_movies_vec: Vec<(usize, f32)> = self
-                .movies
-                .iter()
-                .enumerate()
-                .map(|(idx, movie)| {
-                    let similarity = movie.similarity(reference_movie, min_budget, max_budget);
-                    (idx, similarity)
-                })
-                .collect();
-
-            self.similar_movies = similar_movies_vec;
-
-            // Sort movies by similarity score (descending)
-            self.similar_movies
-                .sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+        if !self.query.liked.is_empty() {
+            self.similar_movies = self
+                .case_base
+                .retrieve(&self.query, TOP_N)
+                .unwrap_or_default();
+        } else if self.describing {
+            self.similar_movies = self.case_base.retrieve_query_case(&self.query_case, TOP_N);
         }
     }
 
+    /// Shows the movies matching the description instead of a selected movie
+    fn find_described_movies(&mut self) {
+        self.selected_movie_index = None;
+        self.query.liked.clear();
+        self.describing = true;
+        self.calculate_similarities();
+    }
+
     /// Filters the movies based on the current search query
     ///
     /// Updates filtered_indices to contain only indices of movies
     /// whose titles contain the search query (case insensitive)
     fn filter_movies(&mut self) {
-        let query = self.search_query.to_lowercase();
-        self.filtered_indices = self
-            .movies
-            .iter()
-            .enumerate()
-            .filter(|(_, movie)| movie.title.to_lowercase().contains(&query))
-            .map(|(idx, _)| idx)
-            .collect();
+        self.filtered_indices = self.case_base.search(&self.search_query);
     }
 
     /// Processes any pending movie selection
@@ -163,13 +247,42 @@ impl MovieSimilarityApp {
     /// 3. Recalculates similarities
     /// 4. Updates the search query to the selected movie's title
     /// 5. Filters the movie list accordingly
+    ///
+    /// A pending toggle (Ctrl-click) adds the movie to the query movies, or
+    /// removes it, without changing the search query. A pending dislike
+    /// (thumbs-down) adds the movie to the disliked movies, or removes it.
     fn process_pending_selection(&mut self) {
         if let Some(idx) = self.pending_selection.take() {
             self.selected_movie_index = Some(idx);
+            self.describing = false;
+            self.query.liked = vec![idx];
+            self.query.disliked.retain(|&disliked| disliked != idx);
             self.calculate_similarities();
-            self.search_query = self.movies[idx].title.clone();
+            self.search_query = self.case_base.movies()[idx].title.clone();
             self.filter_movies();
         }
+        if let Some(idx) = self.pending_toggle.take() {
+            if let Some(position) = self.query.liked.iter().position(|&query| query == idx) {
+                self.query.liked.remove(position);
+            } else {
+                self.query.liked.push(idx);
+                self.query.disliked.retain(|&disliked| disliked != idx);
+                self.describing = false;
+            }
+            self.selected_movie_index = self.query.liked.last().copied();
+            if self.query.liked.is_empty() {
+                self.similar_movies.clear();
+            }
+            self.calculate_similarities();
+        }
+        if let Some(idx) = self.pending_dislike.take() {
+            if let Some(position) = self.query.disliked.iter().position(|&query| query == idx) {
+                self.query.disliked.remove(position);
+            } else {
+                self.query.disliked.push(idx);
+            }
+            self.calculate_similarities();
+        }
     }
 
     /// Draws a movie card UI element with appropriate styling
@@ -228,6 +341,388 @@ impl MovieSimilarityApp {
         // Make the card clickable
         response.interact(egui::Sense::click())
     }
+
+    /// Draws a warning banner summarising the rows skipped while loading
+    ///
+    /// # Arguments
+    /// * `ui` - The egui UI to draw on
+    ///
+    /// The banner shows the number of loaded and skipped rows, with a
+    /// collapsible list of the line, column and error of each skipped row.
+    fn draw_load_report(&self, ui: &mut egui::Ui) {
+        egui::Frame::new()
+            .fill(self.theme.selected_bg)
+            .stroke(Stroke::new(1.0, self.theme.primary))
+            .corner_radius(CornerRadius::same(8))
+            .inner_margin(Margin::same(8))
+            .outer_margin(Margin::same(5))
+            .show(ui, |ui| {
+                ui.add(egui::Label::new(
+                    RichText::new(format!("⚠ {}", self.load_report.summary()))
+                        .strong()
+                        .color(self.theme.primary_light),
+                ));
+                ui.collapsing(
+                    RichText::new("Skipped rows")
+                        .size(12.0)
+                        .color(self.theme.text_secondary),
+                    |ui| {
+                        egui::ScrollArea::vertical()
+                            .id_salt("load_report")
+                            .max_height(120.0)
+                            .show(ui, |ui| {
+                                for row in self.load_report.rejected_rows() {
+                                    ui.label(RichText::new(row).color(self.theme.text_secondary));
+                                }
+                            });
+                    },
+                );
+            });
+    }
+
+    /// Draws the "Describe a movie" form used to build a partial query case
+    ///
+    /// # Arguments
+    /// * `ui` - The egui UI to draw on
+    ///
+    /// Genres are picked from checkboxes, keywords and companies from a
+    /// filtered list of the known vocabulary. Budget and year ranges are only
+    /// part of the query while their checkbox is ticked.
+    fn draw_query_case_editor(&mut self, ui: &mut egui::Ui) {
+        ui.collapsing(
+            RichText::new("Describe a movie")
+                .size(14.0)
+                .color(self.theme.primary),
+            |ui| {
+                // Genres
+                ui.label(RichText::new("Genres").color(self.theme.text_secondary));
+                ui.horizontal_wrapped(|ui| {
+                    for genre in self.case_base.genres() {
+                        let genres = &mut self.query_case.genres;
+                        let position = genres.iter().position(|picked| picked.id() == genre.id());
+                        let mut picked = position.is_some();
+                        if ui.checkbox(&mut picked, genre.name()).changed() {
+                            match position {
+                                Some(position) => {
+                                    genres.remove(position);
+                                }
+                                None => genres.push(genre.clone()),
+                            }
+                        }
+                    }
+                });
+
+                // Keywords and production companies
+                pick_items(
+                    ui,
+                    "Keywords",
+                    &mut self.keyword_filter,
+                    self.case_base.keywords(),
+                    &mut self.query_case.keywords,
+                    Keyword::name,
+                );
+                pick_items(
+                    ui,
+                    "Companies",
+                    &mut self.company_filter,
+                    self.case_base.companies(),
+                    &mut self.query_case.production_companies,
+                    Company::name,
+                );
+
+                // Budget range, each end optional
+                ui.horizontal(|ui| {
+                    let budget = &mut self.query_case.budget;
+                    optional_bound(ui, "Budget from", &mut budget.min, 0, |value| {
+                        egui::DragValue::new(value).speed(100_000).prefix("$")
+                    });
+                    optional_bound(ui, "to", &mut budget.max, 100_000_000, |value| {
+                        egui::DragValue::new(value).speed(100_000).prefix("$")
+                    });
+                });
+
+                // Release year range, each end optional
+                ui.horizontal(|ui| {
+                    let years = &mut self.query_case.years;
+                    optional_bound(ui, "Released from", &mut years.min, 1990, |value| {
+                        egui::DragValue::new(value).range(1870..=2100)
+                    });
+                    optional_bound(ui, "to", &mut years.max, 2010, |value| {
+                        egui::DragValue::new(value).range(1870..=2100)
+                    });
+                });
+
+                // Title fragment
+                ui.horizontal(|ui| {
+                    ui.label("Title contains");
+                    ui.text_edit_singleline(self.query_case.title.get_or_insert_with(String::new));
+                });
+
+                ui.horizontal(|ui| {
+                    if ui
+                        .add_enabled(
+                            !self.query_case.is_empty(),
+                            egui::Button::new("Find matching movies"),
+                        )
+                        .clicked()
+                    {
+                        self.find_described_movies();
+                    }
+                    if ui.button("Clear").clicked() {
+                        self.query_case = QueryCase::default();
+                        if self.describing {
+                            self.describing = false;
+                            self.similar_movies.clear();
+                        }
+                    }
+                });
+            },
+        );
+    }
+
+    /// Draws the movies matching the description, when no movie is selected
+    ///
+    /// # Arguments
+    /// * `ui` - The egui UI to draw on
+    ///
+    /// Clicking a result selects it, showing its details and similar movies.
+    fn draw_described_movies(&mut self, ui: &mut egui::Ui) {
+        ui.add(egui::Label::new(
+            RichText::new("Movies matching your description:")
+                .size(18.0)
+                .color(self.theme.primary)
+                .strong(),
+        ));
+        ui.add_space(5.0);
+
+        if self.similar_movies.is_empty() {
+            ui.label(RichText::new("No matching movies").color(self.theme.text_secondary));
+            return;
+        }
+
+        let mut clicked_movie = None;
+        egui::ScrollArea::vertical()
+            .id_salt("described_movies")
+            .show(ui, |ui| {
+                for (count, &(movie_idx, similarity)) in self.similar_movies.iter().enumerate() {
+                    let movie = &self.case_base.movies()[movie_idx];
+                    let response = egui::Frame::new()
+                        .fill(self.theme.card_bg)
+                        .stroke(Stroke::new(1.0, self.theme.border_light))
+                        .corner_radius(CornerRadius::same(6))
+                        .inner_margin(Margin::same(8))
+                        .outer_margin(Margin::same(4))
+                        .show(ui, |ui| {
+                            ui.horizontal(|ui| {
+                                ui.add(egui::Label::new(
+                                    RichText::new(format!("{}.", count + 1))
+                                        .strong()
+                                        .color(self.theme.text_secondary),
+                                ));
+                                ui.add(egui::Label::new(
+                                    RichText::new(&movie.title).color(self.theme.primary),
+                                ));
+                                ui.with_layout(
+                                    egui::Layout::right_to_left(egui::Align::Center),
+                                    |ui| {
+                                        ui.add(egui::Label::new(
+                                            RichText::new(format!(
+                                                "{}%",
+                                                (similarity * 100.0) as i32
+                                            ))
+                                            .color(self.theme.secondary)
+                                            .strong(),
+                                        ));
+                                    },
+                                );
+                            })
+                            .response
+                        })
+                        .inner
+                        .interact(egui::Sense::click());
+
+                    if response.hovered() {
+                        ui.ctx().set_cursor_icon(CursorIcon::PointingHand);
+                    }
+                    if response.clicked() {
+                        clicked_movie = Some(movie_idx);
+                    }
+                }
+            });
+        if clicked_movie.is_some() {
+            self.pending_selection = clicked_movie;
+        }
+    }
+
+    /// Draws why a similar movie was retrieved for the current query
+    ///
+    /// # Arguments
+    /// * `ui` - The egui UI to draw on
+    /// * `movie_idx` - Index of the similar movie being explained
+    ///
+    /// Shows one breakdown per liked movie (or a single one against their
+    /// centroid), each labelled with the movie it compares against, followed
+    /// by the penalty for resembling the closest disliked movie.
+    fn draw_explanation(&self, ui: &mut egui::Ui, movie_idx: usize) {
+        let Some(explanation) = self.case_base.explain_query(&self.query, movie_idx) else {
+            return;
+        };
+
+        for (reference_idx, breakdown) in &explanation.references {
+            let reference = match reference_idx {
+                Some(reference_idx) => format!(
+                    "Compared with {}: {:.0}%",
+                    self.case_base.movies()[*reference_idx].title,
+                    breakdown.score * 100.0
+                ),
+                None => format!(
+                    "Compared with the centroid of the liked movies: {:.0}%",
+                    breakdown.score * 100.0
+                ),
+            };
+            ui.add(egui::Label::new(
+                RichText::new(reference)
+                    .strong()
+                    .color(self.theme.text_primary),
+            ));
+            self.draw_breakdown(ui, breakdown, (movie_idx, *reference_idx));
+        }
+        if explanation.references.len() > 1 {
+            ui.label(
+                RichText::new(format!(
+                    "Combined ({}): {:.0}%",
+                    self.query.aggregation,
+                    explanation.liked_score * 100.0
+                ))
+                .color(self.theme.text_secondary),
+            );
+        }
+
+        // Penalty for resembling a disliked movie, already part of the score
+        if let Some((disliked_idx, similarity)) = explanation.closest_disliked
+            && explanation.penalty > 0.0
+        {
+            ui.label(
+                RichText::new(format!(
+                    "Closest disliked movie: {} ({:.0}% similar), penalty −{:.0}% ({:.1} × {:.0}%)",
+                    self.case_base.movies()[disliked_idx].title,
+                    similarity * 100.0,
+                    explanation.penalty * 100.0,
+                    self.query.dislike_penalty,
+                    similarity * 100.0
+                ))
+                .color(self.theme.text_secondary),
+            );
+            ui.label(
+                RichText::new(format!(
+                    "Score: {:.0}% − {:.0}% = {:.0}%",
+                    explanation.liked_score * 100.0,
+                    explanation.penalty * 100.0,
+                    explanation.score * 100.0
+                ))
+                .strong()
+                .color(self.theme.text_primary),
+            );
+        }
+    }
+
+    /// Draws the per-attribute breakdown of the similarity between two movies
+    ///
+    /// # Arguments
+    /// * `ui` - The egui UI to draw on
+    /// * `explanation` - The similarity of the movie to one reference
+    /// * `id_salt` - Unique id of the breakdown (movie and reference)
+    ///
+    /// Shows the raw and weighted score of each attribute in a grid, followed
+    /// by the genres, keywords and companies both movies have in common.
+    fn draw_breakdown(
+        &self,
+        ui: &mut egui::Ui,
+        explanation: &SimilarityExplanation,
+        id_salt: (usize, Option<usize>),
+    ) {
+        egui::Grid::new(("explanation_grid", id_salt))
+            .striped(true)
+            .show(ui, |ui| {
+                for header in ["Attribute", "Raw", "Weight", "Weighted"] {
+                    ui.label(
+                        RichText::new(header)
+                            .strong()
+                            .color(self.theme.text_primary),
+                    );
+                }
+                ui.end_row();
+
+                for attribute in &explanation.attributes {
+                    for cell in [
+                        attribute.name.to_string(),
+                        attribute
+                            .raw
+                            .map_or("unknown".to_string(), |raw| format!("{:.0}%", raw * 100.0)),
+                        format!("{:.1}", attribute.weight),
+                        format!("{:.2}", attribute.weighted),
+                    ] {
+                        ui.label(RichText::new(cell).color(self.theme.text_secondary));
+                    }
+                    ui.end_row();
+                }
+            });
+
+        // Shared items behind the Jaccard scores
+        let shared = [
+            (
+                "Shared genres:",
+                explanation
+                    .shared_genres
+                    .iter()
+                    .map(|genre| genre.to_string())
+                    .collect::<Vec<_>>(),
+            ),
+            (
+                "Shared keywords:",
+                explanation
+                    .shared_keywords
+                    .iter()
+                    .map(|keyword| keyword.to_string())
+                    .collect(),
+            ),
+            (
+                "Shared companies:",
+                explanation
+                    .shared_companies
+                    .iter()
+                    .map(|company| company.to_string())
+                    .collect(),
+            ),
+            (
+                "Shared directors:",
+                explanation
+                    .shared_directors
+                    .iter()
+                    .map(|director| director.to_string())
+                    .collect(),
+            ),
+            (
+                "Shared cast:",
+                explanation
+                    .shared_cast
+                    .iter()
+                    .map(|actor| actor.to_string())
+                    .collect(),
+            ),
+        ];
+        for (label, names) in shared {
+            if names.is_empty() {
+                continue;
+            }
+            ui.horizontal_wrapped(|ui| {
+                ui.add(egui::Label::new(
+                    RichText::new(label).strong().color(self.theme.text_primary),
+                ));
+                ui.label(RichText::new(names.join(", ")).color(self.theme.text_secondary));
+            });
+        }
+    }
 }
 
 /// Implementation of the eframe::App trait for MovieSimilarityApp
@@ -271,8 +766,25 @@ impl eframe::App for MovieSimilarityApp {
                 ui.add_space(10.0);
             });
 
+            // Warning banner for rows dropped while loading
+            if !self.load_report.is_clean() {
+                self.draw_load_report(ui);
+            }
+
+            // Error message if the movies could not be loaded at all
+            if let Some(load_error) = &self.load_error {
+                ui.vertical_centered(|ui| {
+                    ui.add_space(50.0);
+                    ui.add(egui::Label::new(
+                        RichText::new(load_error)
+                            .size(16.0)
+                            .color(self.theme.text_secondary),
+                    ));
+                });
+            }
+
             // Main application content (only shown if movies are loaded)
-            if !self.movies.is_empty() {
+            if !self.case_base.is_empty() {
                 // Search bar
                 egui::Frame::new()
                     .fill(self.theme.card_bg)
@@ -312,6 +824,15 @@ impl eframe::App for MovieSimilarityApp {
                                 .color(self.theme.primary)
                                 .strong(),
                         ));
+                        ui.add(egui::Label::new(
+                            RichText::new("Ctrl-click to add more favourites")
+                                .size(12.0)
+                                .color(self.theme.text_secondary),
+                        ));
+                        ui.add_space(5.0);
+
+                        // Partial query case form
+                        self.draw_query_case_editor(ui);
                         ui.add_space(5.0);
 
                         // Scrollable list of movie cards
@@ -319,11 +840,15 @@ impl eframe::App for MovieSimilarityApp {
                             .id_salt("movie_list")
                             .show(ui, |ui| {
                                 for &idx in &self.filtered_indices {
-                                    let movie = &self.movies[idx];
-                                    let selected = Some(idx) == self.selected_movie_index;
+                                    let movie = &self.case_base.movies()[idx];
+                                    let selected = self.query.liked.contains(&idx);
 
                                     if self.draw_card(ui, movie, selected).clicked() {
-                                        self.pending_selection = Some(idx);
+                                        if ui.input(|input| input.modifiers.command) {
+                                            self.pending_toggle = Some(idx);
+                                        } else {
+                                            self.pending_selection = Some(idx);
+                                        }
                                     }
                                 }
                             });
@@ -332,7 +857,7 @@ impl eframe::App for MovieSimilarityApp {
                     // Right column: Selected movie details and similar movies
                     columns[1].vertical(|ui| {
                         if let Some(selected_idx) = self.selected_movie_index {
-                            let selected_movie = &self.movies[selected_idx];
+                            let selected_movie = &self.case_base.movies()[selected_idx];
 
                             // Selected movie details panel
                             egui::Frame::new()
@@ -366,7 +891,11 @@ impl eframe::App for MovieSimilarityApp {
                                         ui.add(egui::Label::new(
                                             RichText::new(format!(
                                                 "Year: {}",
-                                                selected_movie.release_date
+                                                selected_movie
+                                                    .release_year()
+                                                    .map_or("Unknown".to_string(), |year| {
+                                                        year.to_string()
+                                                    })
                                             ))
                                             .size(14.0)
                                             .color(self.theme.text_secondary),
@@ -381,15 +910,33 @@ impl eframe::App for MovieSimilarityApp {
                                         ));
                                     });
 
-                                    // Budget
-                                    ui.add(egui::Label::new(
-                                        RichText::new(format!(
-                                            "Budget: ${}",
-                                            selected_movie.budget
-                                        ))
-                                        .size(14.0)
-                                        .color(self.theme.text_secondary),
-                                    ));
+                                    // Budget (only when known)
+                                    if selected_movie.budget > 0 {
+                                        ui.add(egui::Label::new(
+                                            RichText::new(format!(
+                                                "Budget: ${}",
+                                                selected_movie.budget
+                                            ))
+                                            .size(14.0)
+                                            .color(self.theme.text_secondary),
+                                        ));
+                                    }
+
+                                    // Revenue and runtime (only when known)
+                                    if let Some(revenue) = selected_movie.revenue() {
+                                        ui.add(egui::Label::new(
+                                            RichText::new(format!("Revenue: ${}", revenue))
+                                                .size(14.0)
+                                                .color(self.theme.text_secondary),
+                                        ));
+                                    }
+                                    if let Some(runtime) = selected_movie.runtime() {
+                                        ui.add(egui::Label::new(
+                                            RichText::new(format!("Runtime: {} min", runtime))
+                                                .size(14.0)
+                                                .color(self.theme.text_secondary),
+                                        ));
+                                    }
 
                                     // Collapsible "More Details" section
                                     ui.collapsing(
@@ -397,6 +944,15 @@ impl eframe::App for MovieSimilarityApp {
                                             .size(14.0)
                                             .color(self.theme.primary),
                                         |ui| {
+                                            // Overview
+                                            if !selected_movie.overview().is_empty() {
+                                                ui.label(
+                                                    RichText::new(selected_movie.overview())
+                                                        .italics()
+                                                        .color(self.theme.text_secondary),
+                                                );
+                                            }
+
                                             // Genres
                                             if !selected_movie.genres.is_empty() {
                                                 ui.horizontal_wrapped(|ui| {
@@ -461,12 +1017,254 @@ impl eframe::App for MovieSimilarityApp {
                                                     );
                                                 }
                                             }
+
+                                            // Directors (only available with the credits file)
+                                            let directors = selected_movie.directors();
+                                            if !directors.is_empty() {
+                                                ui.horizontal_wrapped(|ui| {
+                                                    ui.add(egui::Label::new(
+                                                        RichText::new("Directed by:")
+                                                            .strong()
+                                                            .color(self.theme.text_primary),
+                                                    ));
+                                                    for director in directors {
+                                                        ui.label(
+                                                            RichText::new(director.to_string())
+                                                                .color(self.theme.text_secondary),
+                                                        );
+                                                    }
+                                                });
+                                            }
+
+                                            // Top-billed cast (only available with the credits file)
+                                            if !selected_movie.cast.is_empty() {
+                                                ui.add(egui::Label::new(
+                                                    RichText::new("Starring:")
+                                                        .strong()
+                                                        .color(self.theme.text_primary),
+                                                ));
+                                                for actor in selected_movie.top_billed_cast() {
+                                                    ui.label(
+                                                        RichText::new(format!(
+                                                            "{} as {}",
+                                                            actor,
+                                                            actor.character()
+                                                        ))
+                                                        .color(self.theme.text_secondary),
+                                                    );
+                                                }
+                                            }
                                         },
                                     );
                                 });
 
                             ui.add_space(10.0);
 
+                            // Collapsible weight sliders, recalculating on change
+                            let mut weights_changed = false;
+                            let mut measures_changed = false;
+                            let mut weights = self.case_base.weights().clone();
+                            let mut measures = self.case_base.measures().clone();
+                            ui.collapsing(
+                                RichText::new("Similarity Weights")
+                                    .size(14.0)
+                                    .color(self.theme.primary),
+                                |ui| {
+                                    for (label, value) in [
+                                        ("Budget", &mut weights.budget),
+                                        ("Genres", &mut weights.genres),
+                                        ("Homepage", &mut weights.homepage),
+                                        ("Keywords", &mut weights.keywords),
+                                        ("Companies", &mut weights.production_companies),
+                                        ("Title", &mut weights.title),
+                                        ("Director", &mut weights.director),
+                                        ("Cast", &mut weights.cast),
+                                        ("Overview", &mut weights.overview),
+                                        ("Tagline", &mut weights.tagline),
+                                        ("Release date", &mut weights.release_date),
+                                        ("Revenue", &mut weights.revenue),
+                                        ("Runtime", &mut weights.runtime),
+                                        ("Popularity", &mut weights.popularity),
+                                    ] {
+                                        weights_changed |= ui
+                                            .add(egui::Slider::new(value, 0.0..=5.0).text(label))
+                                            .changed();
+                                    }
+
+                                    // Scale used to compare the skewed money attributes
+                                    for (label, scale) in [
+                                        ("Budget scale", &mut measures.budget.scale),
+                                        ("Revenue scale", &mut measures.revenue.scale),
+                                    ] {
+                                        egui::ComboBox::from_label(label)
+                                            .selected_text(scale_label(*scale))
+                                            .show_ui(ui, |ui| {
+                                                for option in [
+                                                    NumericScale::Linear,
+                                                    NumericScale::Log,
+                                                    NumericScale::PercentileRank,
+                                                ] {
+                                                    measures_changed |= ui
+                                                        .selectable_value(
+                                                            scale,
+                                                            option,
+                                                            scale_label(option),
+                                                        )
+                                                        .changed();
+                                                }
+                                            });
+                                    }
+
+                                    // Partial credit for related genres (genre taxonomy)
+                                    let mut related_genres =
+                                        measures.genres.metric == GenreMetric::BestMatch;
+                                    if ui
+                                        .checkbox(
+                                            &mut related_genres,
+                                            "Related genres count partially",
+                                        )
+                                        .on_hover_text(
+                                            "e.g. Action and Adventure, Thriller and Crime",
+                                        )
+                                        .changed()
+                                    {
+                                        measures.genres.metric = if related_genres {
+                                            GenreMetric::BestMatch
+                                        } else {
+                                            GenreMetric::Jaccard
+                                        };
+                                        measures_changed = true;
+                                    }
+
+                                    // Weight shared keywords and companies by their rarity
+                                    for (label, metric) in [
+                                        ("Rare keywords count more", &mut measures.keywords),
+                                        (
+                                            "Rare companies count more",
+                                            &mut measures.production_companies,
+                                        ),
+                                    ] {
+                                        let mut idf = *metric == SetMetric::IdfJaccard;
+                                        if ui.checkbox(&mut idf, label).changed() {
+                                            *metric = if idf {
+                                                SetMetric::IdfJaccard
+                                            } else {
+                                                SetMetric::Jaccard
+                                            };
+                                            measures_changed = true;
+                                        }
+                                    }
+
+                                    // Toggle between candidate generation and exhaustive scan
+                                    let mut exhaustive = self.case_base.is_exhaustive();
+                                    if ui
+                                        .checkbox(&mut exhaustive, "Score every movie (slower)")
+                                        .changed()
+                                    {
+                                        self.case_base.set_exhaustive(exhaustive);
+                                        weights_changed = true;
+                                    }
+                                },
+                            );
+                            if weights_changed {
+                                self.set_weights(weights);
+                            }
+                            if measures_changed {
+                                self.set_measures(measures);
+                            }
+
+                            ui.add_space(10.0);
+
+                            // Query movies and how their similarities are combined
+                            if self.query.liked.len() > 1 {
+                                let titles: Vec<&str> = self
+                                    .query
+                                    .liked
+                                    .iter()
+                                    .map(|&idx| self.case_base.movies()[idx].title.as_str())
+                                    .collect();
+                                ui.add(egui::Label::new(
+                                    RichText::new(format!("More like: {}", titles.join(", ")))
+                                        .size(14.0)
+                                        .color(self.theme.text_secondary),
+                                ));
+
+                                let mut aggregation = self.query.aggregation;
+                                egui::ComboBox::from_label("Combine favourites by")
+                                    .selected_text(aggregation.to_string())
+                                    .show_ui(ui, |ui| {
+                                        for option in Aggregation::ALL {
+                                            ui.selectable_value(
+                                                &mut aggregation,
+                                                option,
+                                                option.to_string(),
+                                            );
+                                        }
+                                    });
+                                if aggregation != self.query.aggregation {
+                                    self.query.aggregation = aggregation;
+                                    self.calculate_similarities();
+                                }
+                                ui.add_space(10.0);
+                            }
+
+                            // Disliked movies (click to remove) and the penalty
+                            if !self.query.disliked.is_empty() {
+                                ui.horizontal_wrapped(|ui| {
+                                    ui.add(egui::Label::new(
+                                        RichText::new("Not like:")
+                                            .size(14.0)
+                                            .color(self.theme.text_secondary),
+                                    ));
+                                    for &idx in &self.query.disliked {
+                                        let title = &self.case_base.movies()[idx].title;
+                                        if ui
+                                            .small_button(format!("{} ✖", title))
+                                            .on_hover_text("Remove from disliked movies")
+                                            .clicked()
+                                        {
+                                            self.pending_dislike = Some(idx);
+                                        }
+                                    }
+                                });
+
+                                let mut penalty = self.query.dislike_penalty;
+                                if ui
+                                    .add(
+                                        egui::Slider::new(&mut penalty, 0.0..=2.0)
+                                            .text("Dislike penalty"),
+                                    )
+                                    .changed()
+                                {
+                                    self.query.dislike_penalty = penalty;
+                                    self.calculate_similarities();
+                                }
+                                ui.add_space(10.0);
+                            }
+
+                            // Keep sequels of one franchise from filling the list
+                            ui.horizontal(|ui| {
+                                let mut franchise_changed = ui
+                                    .checkbox(
+                                        &mut self.query.one_per_franchise,
+                                        "One movie per franchise",
+                                    )
+                                    .changed();
+                                franchise_changed |= ui
+                                    .checkbox(
+                                        &mut self.query.hide_query_franchise,
+                                        "Hide this franchise",
+                                    )
+                                    .on_hover_text(
+                                        "Leave out sequels and prequels of the selected movies",
+                                    )
+                                    .changed();
+                                if franchise_changed {
+                                    self.calculate_similarities();
+                                }
+                            });
+                            ui.add_space(5.0);
+
                             // Similar movies section
                             ui.add(egui::Label::new(
                                 RichText::new(format!("Top {} Similar Movies:", TOP_N))
@@ -476,24 +1274,16 @@ impl eframe::App for MovieSimilarityApp {
                             ));
                             ui.add_space(5.0);
 
-                            let mut count = 0;
-                            let mut index = 0;
-                            let similar_indices: Vec<(usize, f32)> = self.similar_movies.clone();
-
                             // Scrollable list of similar movies
+                            let mut clicked_movie = None;
+                            let mut disliked_movie = None;
                             egui::ScrollArea::vertical()
                                 .id_salt("similar_movies")
                                 .show(ui, |ui| {
-                                    while count < TOP_N && index < similar_indices.len() {
-                                        let (movie_idx, similarity) = similar_indices[index];
-                                        index += 1;
-
-                                        // Skip the reference movie itself
-                                        if movie_idx == selected_idx {
-                                            continue;
-                                        }
-
-                                        let similar_movie = &self.movies[movie_idx];
+                                    for (count, &(movie_idx, similarity)) in
+                                        self.similar_movies.iter().enumerate()
+                                    {
+                                        let similar_movie = &self.case_base.movies()[movie_idx];
 
                                         // Create a card for each similar movie
                                         let response = egui::Frame::new()
@@ -503,41 +1293,70 @@ impl eframe::App for MovieSimilarityApp {
                                             .inner_margin(Margin::same(8))
                                             .outer_margin(Margin::same(4))
                                             .show(ui, |ui| {
-                                                ui.horizontal(|ui| {
-                                                    // Ranking number
-                                                    ui.add(egui::Label::new(
-                                                        RichText::new(format!("{}.", count + 1))
+                                                let row = ui
+                                                    .horizontal(|ui| {
+                                                        // Ranking number
+                                                        ui.add(egui::Label::new(
+                                                            RichText::new(format!(
+                                                                "{}.",
+                                                                count + 1
+                                                            ))
                                                             .strong()
                                                             .color(self.theme.text_secondary),
-                                                    ));
+                                                        ));
 
-                                                    // Movie title
-                                                    ui.add(egui::Label::new(
-                                                        RichText::new(&similar_movie.title)
-                                                            .color(self.theme.primary),
-                                                    ));
+                                                        // Movie title
+                                                        ui.add(egui::Label::new(
+                                                            RichText::new(&similar_movie.title)
+                                                                .color(self.theme.primary),
+                                                        ));
 
-                                                    // Similarity percentage (right-aligned)
-                                                    ui.with_layout(
-                                                        egui::Layout::right_to_left(
-                                                            egui::Align::Center,
-                                                        ),
-                                                        |ui| {
-                                                            let similarity_percentage =
-                                                                (similarity * 100.0) as i32;
-                                                            ui.add(egui::Label::new(
-                                                                RichText::new(format!(
-                                                                    "{}%",
-                                                                    similarity_percentage
-                                                                ))
-                                                                .color(self.theme.secondary)
-                                                                .strong(),
-                                                            ));
-                                                        },
-                                                    );
+                                                        // Similarity percentage (right-aligned)
+                                                        ui.with_layout(
+                                                            egui::Layout::right_to_left(
+                                                                egui::Align::Center,
+                                                            ),
+                                                            |ui| {
+                                                                let similarity_percentage =
+                                                                    (similarity * 100.0) as i32;
+                                                                ui.add(egui::Label::new(
+                                                                    RichText::new(format!(
+                                                                        "{}%",
+                                                                        similarity_percentage
+                                                                    ))
+                                                                    .color(self.theme.secondary)
+                                                                    .strong(),
+                                                                ));
+                                                            },
+                                                        );
+                                                    })
+                                                    .response;
+
+                                                // Expandable per-attribute breakdown
+                                                egui::CollapsingHeader::new(
+                                                    RichText::new("Why is this similar?")
+                                                        .size(12.0)
+                                                        .color(self.theme.text_secondary),
+                                                )
+                                                .id_salt(("explanation", movie_idx))
+                                                .show(ui, |ui| {
+                                                    self.draw_explanation(ui, movie_idx);
                                                 });
+
+                                                // Thumbs-down: steer away from movies like this one
+                                                if ui
+                                                    .small_button("👎 Not like this")
+                                                    .on_hover_text(
+                                                        "Rank movies similar to this one lower",
+                                                    )
+                                                    .clicked()
+                                                {
+                                                    disliked_movie = Some(movie_idx);
+                                                }
+
+                                                row
                                             })
-                                            .response
+                                            .inner
                                             .interact(egui::Sense::click());
 
                                         // Show pointing hand cursor on hover
@@ -547,11 +1366,18 @@ impl eframe::App for MovieSimilarityApp {
 
                                         // Handle clicks to select this movie
                                         if response.clicked() {
-                                            self.pending_selection = Some(movie_idx);
+                                            clicked_movie = Some(movie_idx);
                                         }
-                                        count += 1;
                                     }
                                 });
+                            if clicked_movie.is_some() {
+                                self.pending_selection = clicked_movie;
+                            }
+                            if disliked_movie.is_some() {
+                                self.pending_dislike = disliked_movie;
+                            }
+                        } else if self.describing {
+                            self.draw_described_movies(ui);
                         } else {
                             // Display a message when no movie is selected
                             ui.vertical_centered(|ui| {
@@ -1,3 +1,5 @@
 pub mod movie;
 pub mod cbr;
+pub mod case_base;
+#[cfg(feature = "gui")]
 pub mod gui;
\ No newline at end of file
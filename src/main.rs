@@ -1,7 +1,24 @@
+mod cli;
+
+use clap::Parser;
+use cli::Cli;
+#[cfg(feature = "gui")]
 use movie_cbr::gui::MovieSimilarityApp;
-use std::path::Path;
 
-fn main() -> Result<(), eframe::Error> {
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let cli = Cli::parse();
+
+    // Run a headless command if one was given
+    if let Some(command) = &cli.command {
+        return cli::run(&cli, command);
+    }
+
+    run_gui(&cli)
+}
+
+/// Launches the GUI, loading the movies and weights given on the command line
+#[cfg(feature = "gui")]
+fn run_gui(cli: &Cli) -> Result<(), Box<dyn std::error::Error>> {
     // Initialize with default options
     let native_options = eframe::NativeOptions {
         ..Default::default()
@@ -9,15 +26,44 @@ fn main() -> Result<(), eframe::Error> {
 
     // Create app and load movies
     let mut app = MovieSimilarityApp::default();
-    let path = "./data/tmdb_5000_movies.csv";
-    if let Err(err) = app.load_movies(Path::new(path)) {
+    if let Err(err) = app.load_movies(&cli.data) {
         eprintln!("Error loading movies: {}", err);
     }
 
+    // Join cast and crew if a credits file is available
+    if let Some(path) = cli::credits_path(cli)
+        && let Err(err) = app.load_credits(&path)
+    {
+        eprintln!("Error loading credits: {}", err);
+    }
+
+    // Load custom similarity weights if a weights file was given
+    if cli.weights.is_some() {
+        match cli::load_weights(cli) {
+            Ok(weights) => app.set_weights(weights),
+            Err(err) => eprintln!("Error loading weights: {}", err),
+        }
+    }
+
+    // Load custom similarity measures if a measures or taxonomy file was given
+    if cli.measures.is_some() || cli.taxonomy.is_some() {
+        match cli::load_measures(cli) {
+            Ok(measures) => app.set_measures(measures),
+            Err(err) => eprintln!("Error loading measures: {}", err),
+        }
+    }
+
     // Run the application
     eframe::run_native(
         "Movie Similarity Finder",
         native_options,
         Box::new(|_cc| Ok(Box::new(app))),
-    )
+    )?;
+    Ok(())
+}
+
+/// Headless builds have no GUI, so a subcommand is required
+#[cfg(not(feature = "gui"))]
+fn run_gui(_cli: &Cli) -> Result<(), Box<dyn std::error::Error>> {
+    Err("built without the `gui` feature; run a subcommand such as `similar`".into())
 }
@@ -1,12 +1,24 @@
-use crate::cbr::{self, HasId};
+use crate::cbr::numeric::{Numeric, NumericDomain, NumericMeasure};
+use crate::cbr::string::StringMeasure;
+use crate::cbr::taxonomy::Taxonomy;
+use crate::cbr::text::{self, TermVector, TfIdf};
+use crate::cbr::{self, AttributeScore, CaseSimilarity, DateDecay, HasId, IdWeights};
+use chrono::{Datelike, NaiveDate};
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 use std::fmt::Display;
+use std::fs;
+use std::path::Path;
+use title::{TitleKey, TitleMeasure};
+
+pub mod title;
 
 /// Represents a movie with all its attributes
 /// Used for case-based reasoning to find similar movies
-#[derive(Debug, Deserialize, Serialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
 pub struct Movie {
     /// Budget of the movie in some currency unit (likely USD)
+    /// Note: 0 means the budget is unknown
     pub budget: u32,
     /// List of genres associated with the movie
     /// Note: Deserialized from JSON string in CSV
@@ -36,11 +48,17 @@ pub struct Movie {
     /// Note: Deserialized from JSON string in CSV
     #[serde(deserialize_with = "deserialize_json_string")]
     production_countries: Vec<Country>,
-    /// Release date of the movie (format: YYYY-MM-DD)
-    pub release_date: String,
-    /// Box office revenue of the movie
-    revenue: String,
-    /// Duration of the movie in minutes (may be null)
+    /// Release date of the movie (None if empty or invalid)
+    /// Note: Parsed from the YYYY-MM-DD string in CSV
+    #[serde(deserialize_with = "deserialize_date")]
+    pub release_date: Option<NaiveDate>,
+    /// Box office revenue of the movie (None if unknown)
+    /// Note: A zero or empty cell in CSV means unknown
+    #[serde(deserialize_with = "deserialize_positive")]
+    revenue: Option<u64>,
+    /// Duration of the movie in minutes (None if unknown)
+    /// Note: A zero or empty cell in CSV means unknown
+    #[serde(deserialize_with = "deserialize_positive")]
     runtime: Option<f32>,
     /// Languages spoken in the movie
     /// Note: Deserialized from JSON string in CSV
@@ -56,6 +74,36 @@ pub struct Movie {
     pub vote_average: f32,
     /// Number of user votes/ratings
     pub vote_count: u32,
+    /// Cast of the movie, sorted by billing order
+    /// Note: Not part of the movies CSV, joined from the credits CSV
+    #[serde(skip)]
+    pub cast: Vec<CastMember>,
+    /// Crew of the movie (director, writers, composer, ...)
+    /// Note: Not part of the movies CSV, joined from the credits CSV
+    #[serde(skip)]
+    pub crew: Vec<CrewMember>,
+    /// TF-IDF vector of the overview, computed by `index_text`
+    #[serde(skip)]
+    overview_terms: TermVector,
+    /// TF-IDF vector of the tagline, computed by `index_text`
+    #[serde(skip)]
+    tagline_terms: TermVector,
+    /// Parsed display title, computed by `index_titles` (None without words)
+    #[serde(skip)]
+    title_key: Option<TitleKey>,
+    /// Parsed original title, computed by `index_titles` (None without words
+    /// or when it parses like the display title)
+    #[serde(skip)]
+    original_title_key: Option<TitleKey>,
+    /// Genres sorted by ID without duplicates, computed by `sort_ids`
+    #[serde(skip)]
+    sorted_genres: Vec<Genre>,
+    /// Keywords sorted by ID without duplicates, computed by `sort_ids`
+    #[serde(skip)]
+    sorted_keywords: Vec<Keyword>,
+    /// Production companies sorted by ID without duplicates, computed by `sort_ids`
+    #[serde(skip)]
+    sorted_companies: Vec<Company>,
 }
 
 /// Implementation of HasId trait for Movie
@@ -84,6 +132,13 @@ impl Display for Genre {
     }
 }
 
+impl Genre {
+    /// Returns the human-readable name of the genre
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
 /// Implementation of HasId trait for Genre
 /// Allows Genre objects to be used in similarity calculations
 impl HasId for Genre {
@@ -102,6 +157,13 @@ pub struct Keyword {
     name: String,
 }
 
+impl Keyword {
+    /// Returns the human-readable name of the keyword
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
 /// Implementation of HasId trait for Keyword
 /// Allows Keyword objects to be used in similarity calculations
 impl HasId for Keyword {
@@ -128,6 +190,13 @@ pub struct Company {
     name: String,
 }
 
+impl Company {
+    /// Returns the human-readable name of the company
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
 /// Implementation of HasId trait for Company
 /// Allows Company objects to be used in similarity calculations
 impl HasId for Company {
@@ -145,6 +214,115 @@ impl Display for Company {
     }
 }
 
+/// Represents an actor in the cast of a movie
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct CastMember {
+    /// Unique identifier for the person
+    id: u32,
+    /// Name of the actor
+    name: String,
+    /// Name of the character played
+    character: String,
+    /// Billing order (0 is the top-billed actor)
+    order: u32,
+}
+
+impl CastMember {
+    /// Returns the name of the actor
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the name of the character played
+    pub fn character(&self) -> &str {
+        &self.character
+    }
+
+    /// Returns the billing order (0 is the top-billed actor)
+    pub fn order(&self) -> u32 {
+        self.order
+    }
+}
+
+/// Implementation of HasId trait for CastMember
+/// Allows CastMember objects to be used in similarity calculations
+impl HasId for CastMember {
+    /// Returns the unique identifier for this person
+    fn id(&self) -> u32 {
+        self.id
+    }
+}
+
+/// Implements Display trait for CastMember to allow printing
+impl Display for CastMember {
+    /// Returns the name of the actor when displayed
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
+
+/// Represents a member of the crew of a movie
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct CrewMember {
+    /// Unique identifier for the person
+    id: u32,
+    /// Name of the crew member
+    name: String,
+    /// Job on the movie (e.g., "Director", "Screenplay", "Original Music Composer")
+    job: String,
+    /// Department of the job (e.g., "Directing", "Writing", "Sound")
+    department: String,
+}
+
+impl CrewMember {
+    /// Returns the name of the crew member
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the job on the movie (e.g., "Director")
+    pub fn job(&self) -> &str {
+        &self.job
+    }
+
+    /// Returns the department of the job (e.g., "Directing")
+    pub fn department(&self) -> &str {
+        &self.department
+    }
+}
+
+/// Implementation of HasId trait for CrewMember
+/// Allows CrewMember objects to be used in similarity calculations
+impl HasId for CrewMember {
+    /// Returns the unique identifier for this person
+    fn id(&self) -> u32 {
+        self.id
+    }
+}
+
+/// Implements Display trait for CrewMember to allow printing
+impl Display for CrewMember {
+    /// Returns the name of the crew member when displayed
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
+
+/// A row of the TMDB credits CSV, joined to a Movie by id
+#[derive(Debug, Deserialize, Clone)]
+pub struct Credits {
+    /// Identifier of the movie these credits belong to
+    pub movie_id: u32,
+    /// Cast of the movie
+    /// Note: Deserialized from JSON string in CSV
+    #[serde(deserialize_with = "deserialize_json_string")]
+    pub cast: Vec<CastMember>,
+    /// Crew of the movie
+    /// Note: Deserialized from JSON string in CSV
+    #[serde(deserialize_with = "deserialize_json_string")]
+    pub crew: Vec<CrewMember>,
+}
+
 /// Represents a country
 #[derive(Debug, Deserialize, Serialize, Clone)]
 struct Country {
@@ -184,64 +362,1265 @@ where
     serde_json::from_str(&s).map_err(serde::de::Error::custom)
 }
 
-/// Weight constants for similarity calculation
-/// Higher values give more importance to that attribute when calculating similarity
-const BUDGET_WEIGHT: f32 = 0.3;
-const GENRES_WEIGHT: f32 = 1.0;
-const HOMEPAGE_WEIGHT: f32 = 0.2;
-const KEYWORDS_WEIGHT: f32 = 2.0;
-const PRODUCTION_COMPANIES_WEIGHT: f32 = 1.0;
-const TITLE_WEIGHT: f32 = 2.5;
-/// Sum of all weights used for normalization
-const TOTAL_WEIGHT: f32 = BUDGET_WEIGHT
-    + GENRES_WEIGHT
-    + HOMEPAGE_WEIGHT
-    + KEYWORDS_WEIGHT
-    + PRODUCTION_COMPANIES_WEIGHT
-    + TITLE_WEIGHT;
+/// Custom deserializer function for parsing release dates in CSV cells
+///
+/// Dates are expected in the YYYY-MM-DD format. Empty or invalid cells
+/// become `None` instead of failing the whole row.
+///
+/// # Arguments
+/// * `deserializer` - The deserializer provided by serde
+///
+/// # Returns
+/// * `Result<Option<NaiveDate>, D::Error>` - The parsed date, if any
+fn deserialize_date<'de, D>(deserializer: D) -> Result<Option<NaiveDate>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let s = String::deserialize(deserializer)?;
+    Ok(NaiveDate::parse_from_str(s.trim(), "%Y-%m-%d").ok())
+}
+
+/// Custom deserializer function for optional positive numbers in CSV cells
+///
+/// TMDB uses 0 (or an empty cell) when a value such as revenue or runtime
+/// is unknown. Those become `None` so they are not mistaken for real values.
+///
+/// # Arguments
+/// * `deserializer` - The deserializer provided by serde
+///
+/// # Returns
+/// * `Result<Option<T>, D::Error>` - The value, or None if zero or empty
+fn deserialize_positive<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: serde::Deserialize<'de> + Default + PartialOrd,
+{
+    let value: Option<T> = Option::deserialize(deserializer)?;
+    Ok(value.filter(|value| *value > T::default()))
+}
+
+/// Reads a TOML or JSON configuration file, chosen from the file extension
+fn read_config<T: DeserializeOwned>(path: &Path) -> Result<T, Box<dyn std::error::Error>> {
+    let contents = fs::read_to_string(path)?;
+    match path.extension().and_then(|ext| ext.to_str()) {
+        Some("toml") => Ok(toml::from_str(&contents)?),
+        Some("json") => Ok(serde_json::from_str(&contents)?),
+        _ => Err(format!(
+            "unsupported configuration file format: {} (expected .toml or .json)",
+            path.display()
+        )
+        .into()),
+    }
+}
+
+/// Weights applied to each attribute when calculating movie similarity
+///
+/// Higher values give more importance to that attribute. The weights can be
+/// changed at runtime or loaded from a TOML/JSON file, so tuning no longer
+/// requires recompiling the crate. Attributes missing from a file keep their
+/// default weight.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct SimilarityWeights {
+    /// Weight of the budget comparison
+    pub budget: f32,
+    /// Weight of the genre overlap
+    pub genres: f32,
+    /// Weight of the homepage comparison
+    pub homepage: f32,
+    /// Weight of the keyword overlap
+    pub keywords: f32,
+    /// Weight of the production company overlap
+    pub production_companies: f32,
+    /// Weight of the title comparison
+    pub title: f32,
+    /// Weight of the director overlap (requires the credits file)
+    pub director: f32,
+    /// Weight of the top-billed cast overlap (requires the credits file)
+    pub cast: f32,
+    /// Weight of the plot overview text similarity
+    pub overview: f32,
+    /// Weight of the tagline text similarity
+    pub tagline: f32,
+    /// Weight of the release date proximity
+    pub release_date: f32,
+    /// Weight of the revenue comparison
+    pub revenue: f32,
+    /// Weight of the runtime comparison
+    pub runtime: f32,
+    /// Weight of the popularity comparison
+    pub popularity: f32,
+}
+
+/// Default weights, tuned by hand on the TMDB 5000 dataset
+impl Default for SimilarityWeights {
+    fn default() -> Self {
+        SimilarityWeights {
+            budget: 0.3,
+            genres: 1.0,
+            homepage: 0.2,
+            keywords: 2.0,
+            production_companies: 1.0,
+            title: 2.5,
+            director: 1.5,
+            cast: 1.0,
+            overview: 1.5,
+            tagline: 0.5,
+            release_date: 0.5,
+            revenue: 0.3,
+            runtime: 0.2,
+            popularity: 0.3,
+        }
+    }
+}
+
+impl SimilarityWeights {
+    /// Loads weights from a TOML or JSON file
+    ///
+    /// The format is chosen from the file extension (`.toml` or `.json`).
+    ///
+    /// # Arguments
+    /// * `path` - Path to the weights file
+    ///
+    /// # Returns
+    /// * `Result<SimilarityWeights, Box<dyn std::error::Error>>` - The weights or an
+    ///   error, also when a weight is negative or not a finite number
+    pub fn from_path(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        let weights: SimilarityWeights = read_config(path)?;
+        weights.validate()?;
+        Ok(weights)
+    }
+
+    /// Checks that every weight is a finite, non-negative number
+    ///
+    /// A negative weight would turn matches into penalties and could push
+    /// every score to 0.0, so such weights are rejected instead.
+    ///
+    /// # Returns
+    /// * `Result<(), String>` - An error naming the first invalid weight
+    pub fn validate(&self) -> Result<(), String> {
+        for (name, weight) in self.named() {
+            if !weight.is_finite() || weight < 0.0 {
+                return Err(format!(
+                    "invalid weight `{}` = {} (expected a finite number >= 0)",
+                    name, weight
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Every weight with the name of its attribute
+    fn named(&self) -> [(&'static str, f32); 14] {
+        [
+            ("budget", self.budget),
+            ("genres", self.genres),
+            ("homepage", self.homepage),
+            ("keywords", self.keywords),
+            ("production_companies", self.production_companies),
+            ("title", self.title),
+            ("director", self.director),
+            ("cast", self.cast),
+            ("overview", self.overview),
+            ("tagline", self.tagline),
+            ("release_date", self.release_date),
+            ("revenue", self.revenue),
+            ("runtime", self.runtime),
+            ("popularity", self.popularity),
+        ]
+    }
+}
+
+/// Local similarity measures used for attributes that can be compared in
+/// more than one way
+///
+/// Like the weights, the measures can be changed at runtime or loaded from a
+/// TOML/JSON file. Attributes missing from a file keep their default measure.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Default)]
+#[serde(default)]
+pub struct SimilarityMeasures {
+    /// How the release date similarity decays with the years between movies
+    pub release_date: DateDecay,
+    /// How budgets are compared
+    pub budget: NumericMeasure,
+    /// How revenues are compared
+    pub revenue: NumericMeasure,
+    /// How runtimes are compared
+    pub runtime: NumericMeasure,
+    /// How popularity scores are compared
+    pub popularity: NumericMeasure,
+    /// How titles are compared
+    pub title: TitleMeasure,
+    /// How homepage URLs are compared
+    pub homepage: StringMeasure,
+    /// How genre lists are compared
+    pub genres: GenreMeasure,
+    /// How keyword lists are compared
+    pub keywords: SetMetric,
+    /// How production company lists are compared
+    pub production_companies: SetMetric,
+}
+
+impl SimilarityMeasures {
+    /// Loads measures from a TOML or JSON file
+    ///
+    /// The format is chosen from the file extension (`.toml` or `.json`).
+    ///
+    /// # Arguments
+    /// * `path` - Path to the measures file
+    ///
+    /// # Returns
+    /// * `Result<SimilarityMeasures, Box<dyn std::error::Error>>` - The measures or an error
+    pub fn from_path(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        read_config(path)
+    }
+}
+
+/// How two lists of IDs (keywords, production companies) are compared
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum SetMetric {
+    /// Share of common IDs, every ID counting the same (Jaccard index)
+    Jaccard,
+    /// Share of common IDs weighted by their rarity in the case base, so
+    /// sharing "time loop" counts more than sharing "independent film"
+    #[default]
+    IdfJaccard,
+}
+
+impl SetMetric {
+    /// Calculates the similarity of two lists
+    ///
+    /// # Arguments
+    /// * `a` - Items of the first movie
+    /// * `b` - Items of the second movie
+    /// * `weights` - Rarity of each ID in the dataset (used by `IdfJaccard`)
+    ///
+    /// # Returns
+    /// * `f32` - A value between 0.0 and 1.0
+    pub fn similarity<T: HasId>(self, a: &[T], b: &[T], weights: &IdWeights) -> f32 {
+        match self {
+            SetMetric::Jaccard => cbr::similarity_id(a, b),
+            SetMetric::IdfJaccard => cbr::similarity_weighted_id(a, b, weights),
+        }
+    }
+
+    /// Calculates how well the items of a movie cover the wanted items
+    ///
+    /// # Arguments
+    /// * `query` - The wanted items
+    /// * `items` - Items of the movie
+    /// * `weights` - Rarity of each ID in the dataset (used by `IdfJaccard`)
+    ///
+    /// # Returns
+    /// * `f32` - A value between 0.0 and 1.0
+    pub fn coverage<T: HasId>(self, query: &[T], items: &[T], weights: &IdWeights) -> f32 {
+        match self {
+            SetMetric::Jaccard => cbr::similarity_coverage(query, items),
+            SetMetric::IdfJaccard => cbr::similarity_weighted_coverage(query, items, weights),
+        }
+    }
+}
+
+/// Genre taxonomy shipped with the crate (data/genre_taxonomy.toml)
+const DEFAULT_GENRE_TAXONOMY: &str = include_str!("../data/genre_taxonomy.toml");
+
+/// How two genre lists are combined into one similarity
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum GenreMetric {
+    /// Share of identical genres (Jaccard index)
+    Jaccard,
+    /// Best-match average over the taxonomy: related genres get partial credit
+    #[default]
+    BestMatch,
+}
+
+/// Local measure of the genre attribute
+///
+/// By default genres are compared through the genre taxonomy, so "Action"
+/// and "Adventure" count as close rather than as different genres.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct GenreMeasure {
+    /// How the genre lists are combined
+    pub metric: GenreMetric,
+    /// Similarities between genres, used by the best-match metric
+    pub taxonomy: Taxonomy,
+}
+
+/// Best-match average over the built-in genre taxonomy
+impl Default for GenreMeasure {
+    fn default() -> Self {
+        GenreMeasure {
+            metric: GenreMetric::default(),
+            taxonomy: toml::from_str(DEFAULT_GENRE_TAXONOMY)
+                .expect("the built-in genre taxonomy is valid"),
+        }
+    }
+}
+
+impl GenreMeasure {
+    /// Replaces the taxonomy with one loaded from a TOML or JSON file
+    ///
+    /// # Arguments
+    /// * `path` - Path to the taxonomy file (see data/genre_taxonomy.toml)
+    ///
+    /// # Returns
+    /// * `Result<(), Box<dyn std::error::Error>>` - An error if the file cannot
+    ///   be read or the taxonomy is invalid (unknown parent, cycle, ...)
+    pub fn load_taxonomy(&mut self, path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+        self.taxonomy = read_config(path)?;
+        Ok(())
+    }
+
+    /// Calculates the similarity of two genre lists
+    ///
+    /// # Arguments
+    /// * `a` - Genres of the first movie
+    /// * `b` - Genres of the second movie
+    ///
+    /// # Returns
+    /// * `f32` - A value between 0.0 and 1.0
+    pub fn similarity(&self, a: &[Genre], b: &[Genre]) -> f32 {
+        match self.metric {
+            GenreMetric::Jaccard => cbr::similarity_id(a, b),
+            GenreMetric::BestMatch => {
+                cbr::similarity_best_match(a, b, |a, b| self.genre_similarity(a, b))
+            }
+        }
+    }
+
+    /// Calculates how well the genres of a movie cover the wanted genres
+    ///
+    /// # Arguments
+    /// * `query` - The wanted genres
+    /// * `genres` - Genres of the movie
+    ///
+    /// # Returns
+    /// * `f32` - A value between 0.0 and 1.0
+    pub fn coverage(&self, query: &[Genre], genres: &[Genre]) -> f32 {
+        match self.metric {
+            GenreMetric::Jaccard => cbr::similarity_coverage(query, genres),
+            GenreMetric::BestMatch => cbr::similarity_best_match_coverage(query, genres, |a, b| {
+                self.genre_similarity(a, b)
+            }),
+        }
+    }
+
+    /// Similarity of two single genres: identical IDs match, others are
+    /// looked up in the taxonomy by name
+    fn genre_similarity(&self, a: &Genre, b: &Genre) -> f32 {
+        if a.id == b.id {
+            1.0
+        } else {
+            self.taxonomy.similarity(&a.name, &b.name)
+        }
+    }
+}
+
+/// Distributions of the attributes across the dataset
+///
+/// Used to normalize the numeric comparisons (min-max range, log range or
+/// percentile rank) and to weight keywords and companies by their rarity.
+/// Unknown values are ignored, and every range is `(0, 0)` for an empty dataset.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct DatasetStats {
+    /// Known movie budgets
+    pub budget: NumericDomain,
+    /// Known revenues
+    pub revenue: NumericDomain,
+    /// Known runtimes in minutes
+    pub runtime: NumericDomain,
+    /// Popularity scores
+    pub popularity: NumericDomain,
+    /// Rarity of each keyword (IDF over the movies)
+    pub keywords: IdWeights,
+    /// Rarity of each production company (IDF over the movies)
+    pub production_companies: IdWeights,
+}
+
+impl DatasetStats {
+    /// Collects the numeric attributes and the keyword and company
+    /// frequencies of the given movies
+    pub fn from_movies(movies: &[Movie]) -> Self {
+        DatasetStats {
+            budget: NumericDomain::from_values(
+                movies
+                    .iter()
+                    .map(|movie| movie.budget)
+                    .filter(|budget| *budget > 0),
+            ),
+            revenue: NumericDomain::from_values(movies.iter().filter_map(|movie| movie.revenue)),
+            runtime: NumericDomain::from_values(movies.iter().filter_map(|movie| movie.runtime)),
+            popularity: NumericDomain::from_values(movies.iter().map(|movie| movie.popularity)),
+            keywords: IdWeights::from_cases(movies.iter().map(|movie| &movie.keywords[..])),
+            production_companies: IdWeights::from_cases(
+                movies.iter().map(|movie| &movie.production_companies[..]),
+            ),
+        }
+    }
+}
+
+/// Returns the mean of the values, or None if there are none
+fn mean<N: Numeric>(values: impl IntoIterator<Item = N>) -> Option<f64> {
+    let (sum, count) = values.into_iter().fold((0.0, 0), |(sum, count), value| {
+        (sum + value.to_f64(), count + 1)
+    });
+    (count > 0).then(|| sum / count as f64)
+}
+
+/// Returns the items found in at least half of `total` lists, most frequent first
+fn majority<'a, T: HasId + Clone + 'a>(
+    lists: impl IntoIterator<Item = &'a [T]>,
+    total: usize,
+) -> Vec<T> {
+    let mut counts: Vec<(T, usize)> = Vec::new();
+    for list in lists {
+        for item in list {
+            match counts.iter_mut().find(|(seen, _)| seen.id() == item.id()) {
+                Some((_, count)) => *count += 1,
+                None => counts.push((item.clone(), 1)),
+            }
+        }
+    }
+    counts.retain(|&(_, count)| count * 2 >= total);
+    counts.sort_by(|(_, a), (_, b)| b.cmp(a));
+    counts.into_iter().map(|(item, _)| item).collect()
+}
+
+/// Returns the items if there are any, or None to mark the attribute unknown
+fn known<T>(items: &[T]) -> Option<&[T]> {
+    (!items.is_empty()).then_some(items)
+}
+
+/// Number of top-billed actors compared by the cast attribute
+pub const TOP_BILLED_CAST: usize = 5;
 
 impl Movie {
+    /// Joins the credits of this movie
+    ///
+    /// The cast is sorted by billing order so that `top_billed_cast` returns
+    /// the leading actors.
+    pub fn set_credits(&mut self, credits: Credits) {
+        self.cast = credits.cast;
+        self.cast.sort_by_key(|member| member.order);
+        self.crew = credits.crew;
+    }
+
+    /// Returns the release year, if the release date is known
+    pub fn release_year(&self) -> Option<i32> {
+        self.release_date.map(|date| date.year())
+    }
+
+    /// Returns the box office revenue, if known
+    pub fn revenue(&self) -> Option<u64> {
+        self.revenue
+    }
+
+    /// Returns the duration in minutes, if known
+    pub fn runtime(&self) -> Option<f32> {
+        self.runtime
+    }
+
+    /// Returns the popularity score
+    pub fn popularity(&self) -> f32 {
+        self.popularity
+    }
+
+    /// Returns the original title of the movie in its native language
+    pub fn original_title(&self) -> &str {
+        &self.original_title
+    }
+
+    /// Returns the non-empty titles compared by a title measure
+    ///
+    /// The display title comes first. The original title is added if the
+    /// measure compares original titles and it differs from the display title.
+    pub fn titles(&self, measure: &TitleMeasure) -> Vec<&str> {
+        let mut titles = vec![self.title.as_str()];
+        if measure.original_title && self.original_title != self.title {
+            titles.push(self.original_title.as_str());
+        }
+        titles.retain(|title| !title.is_empty());
+        titles
+    }
+
+    /// Returns the parsed display title, if it has any words
+    pub fn title_key(&self) -> Option<&TitleKey> {
+        self.title_key.as_ref()
+    }
+
+    /// Returns the parsed titles compared by a title measure
+    ///
+    /// Like `titles`, the display title comes first and the original title is
+    /// only included if the measure compares original titles. Titles without
+    /// words are left out. Empty until `index_titles` is called.
+    pub fn title_keys(&self, measure: &TitleMeasure) -> impl Iterator<Item = &TitleKey> + Clone {
+        let original_title = measure.original_title;
+        self.title_key.iter().chain(
+            self.original_title_key
+                .iter()
+                .filter(move |_| original_title),
+        )
+    }
+
+    /// Parses the display and original titles for the title measure
+    ///
+    /// The display title is read as English, the original title in the
+    /// movie's original language. Until this is called, the title attribute
+    /// is unknown when titles are compared by word.
+    pub fn index_titles(&mut self) {
+        let parse = |title: &str, language: &str| {
+            Some(TitleKey::parse_in(title, language)).filter(|key| !key.is_empty())
+        };
+        self.title_key = parse(&self.title, "en");
+        self.original_title_key = parse(&self.original_title, &self.original_language)
+            .filter(|key| self.title_key.as_ref() != Some(key));
+    }
+
+    /// Returns the brief summary of the movie's plot
+    pub fn overview(&self) -> &str {
+        &self.overview
+    }
+
+    /// Returns the marketing tagline of the movie
+    pub fn tagline(&self) -> &str {
+        &self.tagline
+    }
+
+    /// Computes the TF-IDF vectors of the overview and tagline
+    ///
+    /// # Arguments
+    /// * `model` - The TF-IDF model fitted over the texts of the case base
+    ///
+    /// Until this is called, the overview and tagline attributes score 0.0.
+    pub fn index_text(&mut self, model: &TfIdf) {
+        self.overview_terms = model.vectorize(&self.overview);
+        self.tagline_terms = model.vectorize(&self.tagline);
+    }
+
+    /// Keeps copies of the genres, keywords and production companies sorted
+    /// by ID, without duplicate IDs
+    ///
+    /// Called once when the case base is loaded, so the set comparisons can
+    /// merge the sorted lists instead of building hash sets for every pair.
+    /// The lists themselves keep the TMDB order, which puts the primary genre
+    /// and company first.
+    pub fn sort_ids(&mut self) {
+        fn sorted<T: HasId + Clone>(items: &[T]) -> Vec<T> {
+            let mut items = items.to_vec();
+            items.sort_by_key(HasId::id);
+            items.dedup_by_key(|item| item.id());
+            items
+        }
+        self.sorted_genres = sorted(&self.genres);
+        self.sorted_keywords = sorted(&self.keywords);
+        self.sorted_companies = sorted(&self.production_companies);
+    }
+
+    /// Returns the directors of the movie (usually one)
+    pub fn directors(&self) -> Vec<&CrewMember> {
+        self.crew
+            .iter()
+            .filter(|member| member.job == "Director")
+            .collect()
+    }
+
+    /// Returns the first `TOP_BILLED_CAST` actors by billing order
+    pub fn top_billed_cast(&self) -> &[CastMember] {
+        &self.cast[..self.cast.len().min(TOP_BILLED_CAST)]
+    }
+
+    /// Builds a synthetic movie in the middle of several movies
+    ///
+    /// Used as a single query case for "more like these". The centroid has
+    /// the genres, keywords, companies, directors and top-billed actors shared
+    /// by at least half of the movies, the mean of their known numbers and
+    /// release dates, and the mean of their text vectors. It has no title or
+    /// homepage, so those attributes are unknown when comparing to it.
+    ///
+    /// # Arguments
+    /// * `movies` - The movies to combine (already indexed with `index_text`)
+    pub fn centroid(movies: &[&Movie]) -> Movie {
+        let directors: Vec<Vec<&CrewMember>> =
+            movies.iter().map(|movie| movie.directors()).collect();
+        let mut centroid = Movie {
+            budget: mean(
+                movies
+                    .iter()
+                    .map(|movie| movie.budget)
+                    .filter(|budget| *budget > 0),
+            )
+            .map_or(0, |budget| budget.round() as u32),
+            genres: majority(
+                movies.iter().map(|movie| movie.genres.as_slice()),
+                movies.len(),
+            ),
+            keywords: majority(
+                movies.iter().map(|movie| movie.keywords.as_slice()),
+                movies.len(),
+            ),
+            production_companies: majority(
+                movies
+                    .iter()
+                    .map(|movie| movie.production_companies.as_slice()),
+                movies.len(),
+            ),
+            popularity: mean(movies.iter().map(|movie| movie.popularity)).unwrap_or(0.0) as f32,
+            release_date: mean(
+                movies
+                    .iter()
+                    .filter_map(|movie| movie.release_date)
+                    .map(|date| date.num_days_from_ce()),
+            )
+            .and_then(|days| NaiveDate::from_num_days_from_ce_opt(days.round() as i32)),
+            revenue: mean(movies.iter().filter_map(|movie| movie.revenue))
+                .map(|revenue| revenue.round() as u64),
+            runtime: mean(movies.iter().filter_map(|movie| movie.runtime))
+                .map(|runtime| runtime as f32),
+            cast: majority(
+                movies.iter().map(|movie| movie.top_billed_cast()),
+                movies.len(),
+            ),
+            crew: majority(directors.iter().map(Vec::as_slice), movies.len())
+                .into_iter()
+                .cloned()
+                .collect(),
+            overview_terms: TermVector::centroid(movies.iter().map(|movie| &movie.overview_terms)),
+            tagline_terms: TermVector::centroid(movies.iter().map(|movie| &movie.tagline_terms)),
+            ..Movie::default()
+        };
+        centroid.sort_ids();
+        centroid
+    }
+
+    /// Builds the global similarity measure used to compare movies
+    ///
+    /// Registers budget, genres, homepage, keywords, production companies,
+    /// title, director, top-billed cast, overview, tagline, release date,
+    /// revenue, runtime and popularity as weighted attributes of a
+    /// `CaseSimilarity`. Building the measure once and reusing it is cheaper
+    /// than calling `similarity` for every pair.
+    ///
+    /// An attribute is unknown when either movie lacks it: a zero budget, an
+    /// empty title, homepage, overview or tagline, an empty genre/keyword/company
+    /// list, no credits, or a missing date, revenue or runtime. Unknown
+    /// attributes are dropped from both the weighted sum and the weight total,
+    /// so missing data neither inflates nor deflates the score.
+    ///
+    /// # Arguments
+    /// * `stats` - The numeric attribute distributions in the dataset (for normalization)
+    /// * `weights` - The attribute weights to use
+    /// * `measures` - The local measures of configurable attributes
+    ///
+    /// # Returns
+    /// * `CaseSimilarity<Movie>` - The weighted-sum similarity measure
+    pub fn similarity_measure(
+        stats: &DatasetStats,
+        weights: &SimilarityWeights,
+        measures: &SimilarityMeasures,
+    ) -> CaseSimilarity<Movie> {
+        let release_date_decay = measures.release_date;
+        let (budget_measure, budget_domain) = (measures.budget, stats.budget.clone());
+        let (revenue_measure, revenue_domain) = (measures.revenue, stats.revenue.clone());
+        let (runtime_measure, runtime_domain) = (measures.runtime, stats.runtime.clone());
+        let (popularity_measure, popularity_domain) =
+            (measures.popularity, stats.popularity.clone());
+        let (title_measure, homepage_measure) = (measures.title, measures.homepage);
+        let genre_measure = measures.genres.clone();
+        let (keyword_metric, keyword_weights) = (measures.keywords, stats.keywords.clone());
+        let (company_metric, company_weights) = (
+            measures.production_companies,
+            stats.production_companies.clone(),
+        );
+        CaseSimilarity::new()
+            // Budget similarity (normalized over the known budgets)
+            .optional_attribute(
+                "budget",
+                weights.budget,
+                |movie: &Movie| (movie.budget > 0).then_some(&movie.budget),
+                move |a: &u32, b: &u32| budget_measure.similarity_in(*a, *b, &budget_domain),
+            )
+            // Genre similarity (common and related genres, see `GenreMeasure`)
+            .optional_attribute(
+                "genres",
+                weights.genres,
+                |movie: &Movie| known(&movie.sorted_genres),
+                move |a: &[Genre], b: &[Genre]| genre_measure.similarity(a, b),
+            )
+            // Homepage similarity (string comparison)
+            .optional_attribute(
+                "homepage",
+                weights.homepage,
+                |movie: &Movie| (!movie.homepage.is_empty()).then_some(movie.homepage.as_str()),
+                move |a: &str, b: &str| homepage_measure.similarity(a, b),
+            )
+            // Keyword similarity (based on common keywords, rare ones weighing more)
+            .optional_attribute(
+                "keywords",
+                weights.keywords,
+                |movie: &Movie| known(&movie.sorted_keywords),
+                move |a: &[Keyword], b: &[Keyword]| {
+                    keyword_metric.similarity(a, b, &keyword_weights)
+                },
+            )
+            // Production company similarity
+            .optional_attribute(
+                "production_companies",
+                weights.production_companies,
+                |movie: &Movie| known(&movie.sorted_companies),
+                move |a: &[Company], b: &[Company]| {
+                    company_metric.similarity(a, b, &company_weights)
+                },
+            )
+            // Title similarity (words of the display and original titles)
+            .optional_attribute_fn("title", weights.title, move |a: &Movie, b: &Movie| {
+                if title_measure.tokens {
+                    return title::similarity_best_key(
+                        a.title_keys(&title_measure),
+                        b.title_keys(&title_measure),
+                    );
+                }
+                let (a, b) = (a.titles(&title_measure), b.titles(&title_measure));
+                (!a.is_empty() && !b.is_empty()).then(|| title_measure.similarity_best(&a, &b))
+            })
+            // Director similarity (based on common directors)
+            .optional_attribute_fn("director", weights.director, |a: &Movie, b: &Movie| {
+                let (a, b) = (a.directors(), b.directors());
+                Some(cbr::similarity_id(known(&a)?, known(&b)?))
+            })
+            // Cast similarity (based on common top-billed actors)
+            .optional_attribute(
+                "cast",
+                weights.cast,
+                |movie: &Movie| known(movie.top_billed_cast()),
+                cbr::similarity_id,
+            )
+            // Overview similarity (TF-IDF cosine of the plot text)
+            .optional_attribute(
+                "overview",
+                weights.overview,
+                |movie: &Movie| (!movie.overview_terms.is_empty()).then_some(&movie.overview_terms),
+                text::similarity_cosine,
+            )
+            // Tagline similarity (TF-IDF cosine of the tagline text)
+            .optional_attribute(
+                "tagline",
+                weights.tagline,
+                |movie: &Movie| (!movie.tagline_terms.is_empty()).then_some(&movie.tagline_terms),
+                text::similarity_cosine,
+            )
+            // Release date similarity (decays with the years between the movies)
+            .optional_attribute_fn(
+                "release_date",
+                weights.release_date,
+                move |a: &Movie, b: &Movie| {
+                    Some(cbr::similarity_date(
+                        a.release_date?,
+                        b.release_date?,
+                        release_date_decay,
+                    ))
+                },
+            )
+            // Revenue similarity (normalized over the known revenues)
+            .optional_attribute_fn("revenue", weights.revenue, move |a: &Movie, b: &Movie| {
+                Some(revenue_measure.similarity_in(a.revenue?, b.revenue?, &revenue_domain))
+            })
+            // Runtime similarity (normalized over the known runtimes)
+            .optional_attribute_fn("runtime", weights.runtime, move |a: &Movie, b: &Movie| {
+                Some(runtime_measure.similarity_in(a.runtime?, b.runtime?, &runtime_domain))
+            })
+            // Popularity similarity (normalized over all popularity scores)
+            .attribute_fn(
+                "popularity",
+                weights.popularity,
+                move |a: &Movie, b: &Movie| {
+                    popularity_measure.similarity_in(a.popularity, b.popularity, &popularity_domain)
+                },
+            )
+    }
+
     /// Calculates the similarity between this movie and another movie
     ///
     /// The similarity is based on multiple attributes with different weights.
     /// Each attribute contributes to the overall similarity score based on its importance.
-    /// The final similarity score is normalized to be between 0.0 and 1.0.
+    /// The final similarity score is normalized by the sum of the active weights
+    /// to be between 0.0 and 1.0.
     ///
     /// # Arguments
     /// * `other` - The movie to compare with
-    /// * `min_budget` - The minimum budget in the dataset (for normalization)
-    /// * `max_budget` - The maximum budget in the dataset (for normalization)
+    /// * `stats` - The numeric attribute distributions in the dataset (for normalization)
+    /// * `weights` - The attribute weights to use for this comparison
+    /// * `measures` - The local measures of configurable attributes
     ///
     /// # Returns
     /// * `f32` - A similarity score between 0.0 (completely different) and 1.0 (identical)
     ///
-    pub fn similarity(&self, other: &Movie, min_budget: u32, max_budget: u32) -> f32 {
-        // Calculate budget similarity (normalized by min/max values)
-        let budget_diff = cbr::similarity_number(self.budget, other.budget, max_budget, min_budget)
-            * BUDGET_WEIGHT;
-        // Calculate genre similarity (based on common genres)
-        let genres_diff = cbr::similarity_id(&self.genres, &other.genres) * GENRES_WEIGHT;
-        // Calculate homepage similarity (string comparison)
-        let homepage_diff =
-            cbr::similarity_string(&self.homepage, &other.homepage) * HOMEPAGE_WEIGHT;
-        // Calculate keyword similarity (based on common keywords)
-        let keywords_diff = cbr::similarity_id(&self.keywords, &other.keywords) * KEYWORDS_WEIGHT;
-        // Calculate production company similarity
-        let production_companies_diff =
-            cbr::similarity_id(&self.production_companies, &other.production_companies)
-                * PRODUCTION_COMPANIES_WEIGHT;
-        // Calculate title similarity (string comparison)
-        let title_diff = cbr::similarity_string(&self.title, &other.title) * TITLE_WEIGHT;
-
-        // Sum all the weighted similarities
-        let result = budget_diff
-            + genres_diff
-            + homepage_diff
-            + keywords_diff
-            + production_companies_diff
-            + title_diff;
-
-        // Normalize by total weight to get a value between 0.0 and 1.0
-        result / TOTAL_WEIGHT
+    pub fn similarity(
+        &self,
+        other: &Movie,
+        stats: &DatasetStats,
+        weights: &SimilarityWeights,
+        measures: &SimilarityMeasures,
+    ) -> f32 {
+        Self::similarity_measure(stats, weights, measures).similarity(self, other)
+    }
+
+    /// Explains the similarity between this movie and another movie
+    ///
+    /// Takes the same arguments as `similarity`, but instead of a single score
+    /// returns the raw and weighted score of every attribute together with the
+    /// genres, keywords and production companies both movies share.
+    ///
+    /// # Returns
+    /// * `SimilarityExplanation` - The per-attribute breakdown of the similarity
+    pub fn explain(
+        &self,
+        other: &Movie,
+        stats: &DatasetStats,
+        weights: &SimilarityWeights,
+        measures: &SimilarityMeasures,
+    ) -> SimilarityExplanation {
+        let measure = Self::similarity_measure(stats, weights, measures);
+        SimilarityExplanation {
+            score: measure.similarity(self, other),
+            attributes: measure.scores(self, other),
+            shared_genres: cbr::shared_items(&self.genres, &other.genres),
+            shared_keywords: cbr::shared_items(&self.keywords, &other.keywords),
+            shared_companies: cbr::shared_items(
+                &self.production_companies,
+                &other.production_companies,
+            ),
+            shared_directors: cbr::shared_items(&self.directors(), &other.directors())
+                .into_iter()
+                .cloned()
+                .collect(),
+            shared_cast: cbr::shared_items(self.top_billed_cast(), other.top_billed_cast()),
+        }
+    }
+}
+
+/// Per-attribute breakdown of the similarity between two movies
+///
+/// Answers "why is this similar?" by recording how much each attribute
+/// contributed to the global score.
+#[derive(Debug, Clone)]
+pub struct SimilarityExplanation {
+    /// Global similarity score, between 0.0 and 1.0
+    pub score: f32,
+    /// Raw and weighted score of every attribute
+    pub attributes: Vec<AttributeScore>,
+    /// Genres present in both movies
+    pub shared_genres: Vec<Genre>,
+    /// Keywords present in both movies
+    pub shared_keywords: Vec<Keyword>,
+    /// Production companies present in both movies
+    pub shared_companies: Vec<Company>,
+    /// Directors of both movies
+    pub shared_directors: Vec<CrewMember>,
+    /// Top-billed actors of both movies
+    pub shared_cast: Vec<CastMember>,
+}
+
+/// Wanted values of a numeric attribute, each end optional
+///
+/// A missing end leaves the range open on that side. Without either end the
+/// attribute is not part of the query.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Bounds<T> {
+    /// Smallest wanted value, or None for no lower bound
+    pub min: Option<T>,
+    /// Largest wanted value, or None for no upper bound
+    pub max: Option<T>,
+}
+
+impl<T: Copy + Ord> Bounds<T> {
+    /// Returns true if neither end is set
+    pub fn is_unbounded(&self) -> bool {
+        self.min.is_none() && self.max.is_none()
+    }
+
+    /// Returns the wanted value nearest to the given one
+    ///
+    /// # Arguments
+    /// * `value` - The value to compare with the bounds
+    ///
+    /// # Returns
+    /// * `T` - The value itself if it lies within the bounds, otherwise the
+    ///   bound it passes (the lower one if the bounds are inverted)
+    pub fn nearest(&self, value: T) -> T {
+        let value = self.max.map_or(value, |max| value.min(max));
+        self.min.map_or(value, |min| value.max(min))
+    }
+}
+
+/// A partial description of the wanted movie, used as a query case
+///
+/// Only the attributes that are set take part in retrieval: the similarity is
+/// renormalized over their weights. Genres, keywords and companies match by
+/// how many of the asked-for items a movie has (weighted by rarity for
+/// keywords and companies, with partial credit for related genres), ranges
+/// match fully inside and decay with the distance outside, and the title
+/// fragment matches titles containing it.
+#[derive(Debug, Clone, Default)]
+pub struct QueryCase {
+    /// Wanted genres
+    pub genres: Vec<Genre>,
+    /// Wanted keywords
+    pub keywords: Vec<Keyword>,
+    /// Wanted production companies
+    pub production_companies: Vec<Company>,
+    /// Wanted budget range, open on either side
+    pub budget: Bounds<u32>,
+    /// Wanted release years, open on either side
+    pub years: Bounds<i32>,
+    /// Fragment of the title (case insensitive)
+    pub title: Option<String>,
+}
+
+impl QueryCase {
+    /// Returns true if no attribute is set
+    pub fn is_empty(&self) -> bool {
+        self.genres.is_empty()
+            && self.keywords.is_empty()
+            && self.production_companies.is_empty()
+            && self.budget.is_unbounded()
+            && self.years.is_unbounded()
+            && self.title.as_deref().is_none_or(str::is_empty)
+    }
+
+    /// Builds the similarity measure comparing a query case with movies
+    ///
+    /// Uses the weights of the matching movie attributes ("genres",
+    /// "keywords", "production_companies", "budget", "release_date" and
+    /// "title") and the configured budget, release date and title measures.
+    /// Attributes the query does not set are unknown, so they are left out of
+    /// the score.
+    ///
+    /// # Arguments
+    /// * `stats` - The numeric attribute distributions in the dataset (for normalization)
+    /// * `weights` - The attribute weights to use
+    /// * `measures` - The local measures of configurable attributes
+    pub fn similarity_measure(
+        stats: &DatasetStats,
+        weights: &SimilarityWeights,
+        measures: &SimilarityMeasures,
+    ) -> CaseSimilarity<QueryCase, Movie> {
+        let release_date_decay = measures.release_date;
+        let (budget_measure, budget_domain) = (measures.budget, stats.budget.clone());
+        let title_measure = measures.title;
+        let genre_measure = measures.genres.clone();
+        let (keyword_metric, keyword_weights) = (measures.keywords, stats.keywords.clone());
+        let (company_metric, company_weights) = (
+            measures.production_companies,
+            stats.production_companies.clone(),
+        );
+        CaseSimilarity::new()
+            // Genre coverage (share of the wanted genres the movie has, or
+            // related genres with the taxonomy)
+            .optional_attribute_fn(
+                "genres",
+                weights.genres,
+                move |query: &QueryCase, movie: &Movie| {
+                    Some(
+                        genre_measure.coverage(known(&query.genres)?, known(&movie.sorted_genres)?),
+                    )
+                },
+            )
+            // Keyword coverage
+            .optional_attribute_fn(
+                "keywords",
+                weights.keywords,
+                move |query: &QueryCase, movie: &Movie| {
+                    Some(keyword_metric.coverage(
+                        known(&query.keywords)?,
+                        known(&movie.sorted_keywords)?,
+                        &keyword_weights,
+                    ))
+                },
+            )
+            // Production company coverage
+            .optional_attribute_fn(
+                "production_companies",
+                weights.production_companies,
+                move |query: &QueryCase, movie: &Movie| {
+                    Some(company_metric.coverage(
+                        known(&query.production_companies)?,
+                        known(&movie.sorted_companies)?,
+                        &company_weights,
+                    ))
+                },
+            )
+            // Budget range (1.0 inside, decaying with the distance to the range)
+            .optional_attribute_fn(
+                "budget",
+                weights.budget,
+                move |query: &QueryCase, movie: &Movie| {
+                    if query.budget.is_unbounded() {
+                        return None;
+                    }
+                    let budget = (movie.budget > 0).then_some(movie.budget)?;
+                    let nearest = query.budget.nearest(budget);
+                    Some(budget_measure.similarity_in(budget, nearest, &budget_domain))
+                },
+            )
+            // Release year range (1.0 inside, decaying with the time to the range)
+            .optional_attribute_fn(
+                "release_date",
+                weights.release_date,
+                move |query: &QueryCase, movie: &Movie| {
+                    if query.years.is_unbounded() {
+                        return None;
+                    }
+                    let date = movie.release_date?;
+                    // Years beyond the calendar are as far as the calendar goes
+                    let year = |year: i32| year.clamp(NaiveDate::MIN.year(), NaiveDate::MAX.year());
+                    let dates = Bounds {
+                        min: (query.years.min)
+                            .and_then(|first| NaiveDate::from_ymd_opt(year(first), 1, 1)),
+                        max: (query.years.max)
+                            .and_then(|last| NaiveDate::from_ymd_opt(year(last), 12, 31)),
+                    };
+                    Some(cbr::similarity_date(
+                        date,
+                        dates.nearest(date),
+                        release_date_decay,
+                    ))
+                },
+            )
+            // Title fragment (1.0 if contained, otherwise title similarity)
+            .optional_attribute_fn(
+                "title",
+                weights.title,
+                move |query: &QueryCase, movie: &Movie| {
+                    let fragment = query.title.as_deref()?.trim().to_lowercase();
+                    if fragment.is_empty() {
+                        return None;
+                    }
+                    let titles = movie.titles(&title_measure);
+                    if titles
+                        .iter()
+                        .any(|title| title.to_lowercase().contains(&fragment))
+                    {
+                        Some(1.0)
+                    } else {
+                        Some(title_measure.similarity_best(&[&fragment], &titles))
+                    }
+                },
+            )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn weights_reject_negative_and_non_finite_values() {
+        assert!(SimilarityWeights::default().validate().is_ok());
+
+        let negative = SimilarityWeights {
+            budget: -3.0,
+            ..SimilarityWeights::default()
+        };
+        assert!(negative.validate().unwrap_err().contains("budget"));
+
+        let infinite = SimilarityWeights {
+            title: f32::INFINITY,
+            ..SimilarityWeights::default()
+        };
+        assert!(infinite.validate().unwrap_err().contains("title"));
+
+        let nan = SimilarityWeights {
+            cast: f32::NAN,
+            ..SimilarityWeights::default()
+        };
+        assert!(nan.validate().is_err());
+    }
+
+    #[test]
+    fn weights_file_with_negative_weight_is_rejected() {
+        let path =
+            std::env::temp_dir().join(format!("movie_cbr_weights_{}.toml", std::process::id()));
+        fs::write(&path, "budget = -3\ntitle = -10\n").unwrap();
+        let result = SimilarityWeights::from_path(&path);
+        fs::remove_file(&path).unwrap();
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn sorting_ids_keeps_the_tmdb_order() {
+        let genre = |id: u32, name: &str| Genre {
+            id,
+            name: name.to_string(),
+        };
+        let mut movie = Movie {
+            genres: vec![
+                genre(28, "Action"),
+                genre(12, "Adventure"),
+                genre(28, "Action"),
+            ],
+            ..Movie::default()
+        };
+        movie.sort_ids();
+
+        let names: Vec<&str> = movie.genres.iter().map(Genre::name).collect();
+        assert_eq!(names, ["Action", "Adventure", "Action"]);
+        let ids: Vec<u32> = movie.sorted_genres.iter().map(HasId::id).collect();
+        assert_eq!(ids, [12, 28]);
+    }
+
+    /// A movie released on the given date with the given budget
+    fn movie(budget: u32, release: &str) -> Movie {
+        Movie {
+            budget,
+            release_date: release.parse().ok(),
+            ..Movie::default()
+        }
+    }
+
+    /// Similarity of a movie to a query case, in a case base of the given movies
+    fn query_similarity(query: &QueryCase, movie: &Movie, movies: &[Movie]) -> f32 {
+        let measure = QueryCase::similarity_measure(
+            &DatasetStats::from_movies(movies),
+            &SimilarityWeights::default(),
+            &SimilarityMeasures::default(),
+        );
+        measure.similarity(query, movie)
+    }
+
+    #[test]
+    fn one_sided_year_ranges_are_open_on_the_other_side() {
+        let movies = [movie(0, "1980-06-01"), movie(0, "2000-06-01")];
+        let from = |year| QueryCase {
+            years: Bounds {
+                min: Some(year),
+                max: None,
+            },
+            ..QueryCase::default()
+        };
+        let to = |year| QueryCase {
+            years: Bounds {
+                min: None,
+                max: Some(year),
+            },
+            ..QueryCase::default()
+        };
+
+        assert_eq!(query_similarity(&from(1990), &movies[1], &movies), 1.0);
+        let before = query_similarity(&from(1990), &movies[0], &movies);
+        assert!(before > 0.0 && before < 1.0, "{}", before);
+
+        assert_eq!(query_similarity(&to(1990), &movies[0], &movies), 1.0);
+        let after = query_similarity(&to(1990), &movies[1], &movies);
+        assert!(after > 0.0 && after < 1.0, "{}", after);
+
+        // Years beyond the calendar still leave the range open
+        assert_eq!(query_similarity(&from(i32::MIN), &movies[0], &movies), 1.0);
+        assert_eq!(query_similarity(&to(i32::MAX), &movies[1], &movies), 1.0);
+    }
+
+    #[test]
+    fn one_sided_budget_ranges_are_open_on_the_other_side() {
+        let movies = [
+            movie(1_000_000, "2000-01-01"),
+            movie(50_000_000, "2000-01-01"),
+            movie(200_000_000, "2000-01-01"),
+        ];
+        let budget = |min, max| QueryCase {
+            budget: Bounds { min, max },
+            ..QueryCase::default()
+        };
+
+        let at_least = budget(Some(40_000_000), None);
+        assert_eq!(query_similarity(&at_least, &movies[2], &movies), 1.0);
+        assert!(query_similarity(&at_least, &movies[0], &movies) < 1.0);
+
+        let at_most = budget(None, Some(40_000_000));
+        assert_eq!(query_similarity(&at_most, &movies[0], &movies), 1.0);
+        assert!(query_similarity(&at_most, &movies[2], &movies) < 1.0);
+
+        assert!(budget(None, None).is_empty());
+    }
+
+    #[test]
+    fn titles_without_words_are_an_unknown_attribute() {
+        let titled = |title: &str| {
+            let mut movie = Movie {
+                title: title.to_string(),
+                original_title: title.to_string(),
+                ..Movie::default()
+            };
+            movie.index_titles();
+            movie
+        };
+        let movies = [titled("!!!"), titled("???"), titled("Jaws")];
+        let measure = Movie::similarity_measure(
+            &DatasetStats::from_movies(&movies),
+            &SimilarityWeights::default(),
+            &SimilarityMeasures::default(),
+        );
+        let title_score = |a: &Movie, b: &Movie| {
+            measure
+                .scores(a, b)
+                .into_iter()
+                .find(|score| score.name == "title")
+                .unwrap()
+                .raw
+        };
+        assert_eq!(title_score(&movies[0], &movies[1]), None);
+        assert_eq!(title_score(&movies[0], &movies[2]), None);
+        assert_eq!(title_score(&movies[2], &movies[2]), Some(1.0));
+    }
+
+    #[test]
+    fn blank_or_malformed_dates_are_unknown() {
+        use serde::de::IntoDeserializer;
+        use serde::de::value::{Error, StrDeserializer};
+
+        let parse = |cell: &str| {
+            deserialize_date::<StrDeserializer<Error>>(cell.into_deserializer()).unwrap()
+        };
+        assert_eq!(parse(" 2009-12-10 "), NaiveDate::from_ymd_opt(2009, 12, 10));
+        assert_eq!(parse(""), None);
+        assert_eq!(parse("   "), None);
+        assert_eq!(parse("2009-13-45"), None);
+        assert_eq!(parse("10/12/2009"), None);
+        assert_eq!(parse("December 2009"), None);
+    }
+
+    #[test]
+    fn unknown_release_dates_are_left_out_of_the_total() {
+        let movies = [
+            movie(1_000_000, "2000-01-01"),
+            movie(1_000_000, ""),
+            movie(1_000_000, "1950-01-01"),
+        ];
+        let stats = DatasetStats::from_movies(&movies);
+        let measure = |weights: &SimilarityWeights| {
+            Movie::similarity_measure(&stats, weights, &SimilarityMeasures::default())
+        };
+        let with_dates = measure(&SimilarityWeights::default());
+        let without_dates = measure(&SimilarityWeights {
+            release_date: 0.0,
+            ..SimilarityWeights::default()
+        });
+
+        let release_date = with_dates
+            .scores(&movies[0], &movies[1])
+            .into_iter()
+            .find(|score| score.name == "release_date")
+            .unwrap();
+        assert_eq!(release_date.raw, None);
+        assert_eq!(
+            with_dates.similarity(&movies[0], &movies[1]),
+            without_dates.similarity(&movies[0], &movies[1])
+        );
+        // A known but distant date does lower the score
+        assert!(
+            with_dates.similarity(&movies[0], &movies[2])
+                < with_dates.similarity(&movies[0], &movies[1])
+        );
     }
 }
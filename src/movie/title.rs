@@ -0,0 +1,571 @@
+//! Title comparison that understands articles, sequels and subtitles
+//!
+//! A raw edit distance makes "The Matrix" and "The Mummy" look close (same
+//! article, same length) and "Alien" and "Aliens" nearly identical. Titles
+//! are therefore parsed into a `TitleKey` first:
+//! - the title is NFKC-normalized, lowercased and split into word tokens,
+//! - a subtitle after ":" or " - " is split off from the main title,
+//! - a leading article of the title's language ("The", "Le", "Der", ...) is dropped,
+//! - a trailing installment number ("2", "II", "Part Two", "Vol. 3") is removed
+//!   and kept as a number.
+//!
+//! Two keys with the same main title, ignoring plural endings, are
+//! installments of the same franchise and score `SAME_FRANCHISE`, or 1.0 for
+//! the same title, installment and subtitle. Other titles are compared by the
+//! Dice coefficient of their singular words, so only shared words count,
+//! never shared letters.
+
+use crate::cbr::string::StringMeasure;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use unicode_normalization::UnicodeNormalization;
+
+/// Similarity of two different installments of the same franchise
+/// (e.g. "Toy Story" and "Toy Story 3")
+pub const SAME_FRANCHISE: f32 = 0.9;
+
+/// Articles dropped from the start of a title, by ISO 639-1 language code
+///
+/// Only the title's own language counts: "Die Hard" keeps its "die" and
+/// "La La Land" its "la" in English.
+const LEADING_ARTICLES: &[(&str, &[&str])] = &[
+    ("en", &["the", "a", "an"]),
+    ("fr", &["le", "la", "les", "l", "un", "une"]),
+    ("es", &["el", "la", "los", "las", "un", "una"]),
+    (
+        "it",
+        &["il", "lo", "la", "i", "gli", "le", "l", "un", "una", "uno"],
+    ),
+    ("pt", &["o", "a", "os", "as", "um", "uma"]),
+    ("de", &["der", "die", "das", "ein", "eine"]),
+    ("nl", &["de", "het", "een"]),
+];
+
+/// Words announcing an installment number ("Part 2", "Chapter Three", "Vol. 1")
+const INSTALLMENT_MARKERS: &[&str] = &["part", "chapter", "episode", "vol", "volume", "book"];
+
+/// Numbers written as words, from one
+const NUMBER_WORDS: &[&str] = &[
+    "one", "two", "three", "four", "five", "six", "seven", "eight", "nine", "ten", "eleven",
+    "twelve",
+];
+
+/// Roman numerals, from one
+const ROMAN_NUMERALS: &[&str] = &[
+    "i", "ii", "iii", "iv", "v", "vi", "vii", "viii", "ix", "x", "xi", "xii", "xiii", "xiv", "xv",
+    "xvi", "xvii", "xviii", "xix", "xx",
+];
+
+/// Largest number read as an installment ("Blade Runner 2049" is not a sequel number)
+const MAX_INSTALLMENT: u32 = 20;
+
+/// A title parsed for comparison
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TitleKey {
+    /// Words of the main title, without the leading article and installment number
+    main: Vec<String>,
+    /// Words of the subtitle (after ":" or " - "), without the leading article
+    subtitle: Vec<String>,
+    /// Installment number, if the title ends with one
+    installment: Option<u32>,
+}
+
+impl TitleKey {
+    /// Parses an English title into its main title, subtitle and installment number
+    ///
+    /// # Arguments
+    /// * `title` - The title to parse
+    ///
+    /// # Returns
+    /// * `TitleKey` - The parsed title (empty for an empty title)
+    pub fn parse(title: &str) -> Self {
+        Self::parse_in(title, "en")
+    }
+
+    /// Parses a title written in the given language
+    ///
+    /// # Arguments
+    /// * `title` - The title to parse
+    /// * `language` - ISO 639-1 code of the title's language (e.g. "fr"); no
+    ///   article is dropped for a language without a known article list
+    ///
+    /// # Returns
+    /// * `TitleKey` - The parsed title (empty for an empty title)
+    pub fn parse_in(title: &str, language: &str) -> Self {
+        let articles = LEADING_ARTICLES
+            .iter()
+            .find(|(code, _)| code.eq_ignore_ascii_case(language))
+            .map_or(&[][..], |(_, articles)| *articles);
+        let title: String = title.nfkc().collect::<String>().to_lowercase();
+        let (main, subtitle) = split_subtitle(&title);
+        let (mut main, mut subtitle) = (tokenize(main), tokenize(subtitle));
+
+        // The number usually ends the main title ("Toy Story 3"), but may end
+        // the subtitle ("Mission: Impossible II", "Kill Bill: Vol. 2")
+        let installment = if subtitle.is_empty() {
+            take_installment(&mut main, true)
+        } else {
+            take_installment(&mut subtitle, false)
+        };
+
+        TitleKey {
+            main: strip_article(main, articles),
+            subtitle: strip_article(subtitle, articles),
+            installment,
+        }
+    }
+
+    /// Returns the words of the main title
+    pub fn main(&self) -> &[String] {
+        &self.main
+    }
+
+    /// Returns the words of the subtitle
+    pub fn subtitle(&self) -> &[String] {
+        &self.subtitle
+    }
+
+    /// Returns the installment number, if the title ends with one
+    pub fn installment(&self) -> Option<u32> {
+        self.installment
+    }
+
+    /// Returns true if the title has no words
+    pub fn is_empty(&self) -> bool {
+        self.main.is_empty() && self.subtitle.is_empty()
+    }
+
+    /// Returns true if both titles name installments of the same franchise,
+    /// i.e. they have the same `stem` ("Alien" and "Aliens")
+    pub fn same_franchise(&self, other: &TitleKey) -> bool {
+        !self.main.is_empty()
+            && self.main.len() == other.main.len()
+            && self
+                .main
+                .iter()
+                .zip(&other.main)
+                .all(|(a, b)| singular(a) == singular(b))
+    }
+
+    /// Main title with plural endings removed, shared by the installments of
+    /// a franchise ("Alien" and "Aliens" both give "alien")
+    pub fn stem(&self) -> String {
+        self.main
+            .iter()
+            .map(|word| singular(word))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    /// All words of the title in the singular, main title first
+    fn words(&self) -> impl Iterator<Item = &str> {
+        self.main
+            .iter()
+            .chain(&self.subtitle)
+            .map(|word| singular(word))
+    }
+}
+
+/// Splits a title at the first ":" or dash separating a subtitle
+fn split_subtitle(title: &str) -> (&str, &str) {
+    [":", " - ", " – ", " — "]
+        .iter()
+        .filter_map(|separator| {
+            title
+                .find(separator)
+                .map(|position| (position, separator.len()))
+        })
+        .min()
+        .map_or((title, ""), |(position, length)| {
+            (&title[..position], &title[position + length..])
+        })
+}
+
+/// Splits a title into alphanumeric word tokens
+fn tokenize(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|token| !token.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// Drops a leading article, unless it is the only word ("A" stays "a")
+fn strip_article(mut tokens: Vec<String>, articles: &[&str]) -> Vec<String> {
+    if tokens.len() > 1 && articles.contains(&tokens[0].as_str()) {
+        tokens.remove(0);
+    }
+    tokens
+}
+
+/// Removes a plural "s" from a word ("aliens", but not "boss" or "bus")
+fn singular(word: &str) -> &str {
+    match word.strip_suffix('s') {
+        Some(stem) if stem.chars().count() > 2 && !stem.ends_with('s') => stem,
+        _ => word,
+    }
+}
+
+/// Removes a trailing installment number (and its "Part"/"Vol." marker)
+///
+/// With `keep_word`, a title that would be left empty keeps its number
+/// ("Seven", "2012"); a subtitle may be emptied ("Kill Bill: Vol. 2").
+/// A number glued to the last word is split off first ("Alien³" is NFKC
+/// normalized to "alien3"). Single-letter numerals count after a marker or
+/// after other words ("Rocky V"), except "X", which is more often a letter
+/// ("Malcolm X").
+fn take_installment(tokens: &mut Vec<String>, keep_word: bool) -> Option<u32> {
+    split_glued_number(tokens);
+    let (last, rest) = tokens.split_last()?;
+    let marked = rest
+        .last()
+        .is_some_and(|token| INSTALLMENT_MARKERS.contains(&token.as_str()));
+    let single_letter = marked || (!rest.is_empty() && last != "x");
+    let number = parse_number(last, single_letter)?;
+
+    let kept = rest.len() - usize::from(marked);
+    if keep_word && kept == 0 {
+        return None;
+    }
+    tokens.truncate(kept);
+    Some(number)
+}
+
+/// Splits an installment number off the end of the last word ("alien3")
+fn split_glued_number(tokens: &mut Vec<String>) {
+    let Some(last) = tokens.last() else {
+        return;
+    };
+    let digits = last.len() - last.trim_end_matches(|c: char| c.is_ascii_digit()).len();
+    let (word, number) = last.split_at(last.len() - digits);
+    let installment = number
+        .parse::<u32>()
+        .is_ok_and(|number| (1..=MAX_INSTALLMENT).contains(&number));
+    if installment && !word.is_empty() && word.chars().all(char::is_alphabetic) {
+        let (word, number) = (word.to_string(), number.to_string());
+        tokens.pop();
+        tokens.extend([word, number]);
+    }
+}
+
+/// Reads a digit, word or roman installment number between 1 and `MAX_INSTALLMENT`
+///
+/// Single-letter roman numerals ("I", "V") are only read with `single_letter`.
+fn parse_number(token: &str, single_letter: bool) -> Option<u32> {
+    let position = |list: &[&str]| list.iter().position(|item| *item == token);
+    let number = if let Ok(number) = token.parse::<u32>() {
+        number
+    } else if let Some(index) = position(NUMBER_WORDS) {
+        index as u32 + 1
+    } else if let Some(index) =
+        position(ROMAN_NUMERALS).filter(|_| single_letter || token.len() > 1)
+    {
+        index as u32 + 1
+    } else {
+        return None;
+    };
+    (1..=MAX_INSTALLMENT).contains(&number).then_some(number)
+}
+
+/// Dice coefficient of two multisets of words
+fn word_dice<'a>(a: impl Iterator<Item = &'a str>, b: impl Iterator<Item = &'a str>) -> f32 {
+    let mut counts: HashMap<&str, usize> = HashMap::new();
+    let mut a_len = 0;
+    for word in a {
+        *counts.entry(word).or_default() += 1;
+        a_len += 1;
+    }
+    let (mut shared, mut b_len) = (0, 0);
+    for word in b {
+        if let Some(count) = counts.get_mut(word).filter(|count| **count > 0) {
+            *count -= 1;
+            shared += 1;
+        }
+        b_len += 1;
+    }
+    if a_len + b_len == 0 {
+        return 0.0;
+    }
+    2.0 * shared as f32 / (a_len + b_len) as f32
+}
+
+/// Calculates the similarity of two parsed titles
+///
+/// # Arguments
+/// * `a` - First title
+/// * `b` - Second title
+///
+/// # Returns
+/// * `f32` - 1.0 for the same title, `SAME_FRANCHISE` for another installment
+///   of the same franchise, otherwise the share of common words (0.0 for
+///   titles without words, which callers should treat as unknown)
+pub fn similarity_title(a: &TitleKey, b: &TitleKey) -> f32 {
+    if a.same_franchise(b) {
+        let same_installment = a.installment.unwrap_or(1) == b.installment.unwrap_or(1);
+        if same_installment && a.main == b.main && a.subtitle == b.subtitle {
+            1.0
+        } else {
+            SAME_FRANCHISE
+        }
+    } else {
+        word_dice(a.words(), b.words())
+    }
+}
+
+/// How titles are compared
+///
+/// By default the titles are parsed into `TitleKey`s and compared by word,
+/// and the original (native language) titles are compared too. With
+/// `tokens` off, the raw titles are compared with the string measure.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct TitleMeasure {
+    /// Compare the parsed titles word by word
+    pub tokens: bool,
+    /// Also compare the original titles and keep the best match
+    pub original_title: bool,
+    /// Measure of the raw titles, used when `tokens` is off
+    #[serde(flatten)]
+    pub string: StringMeasure,
+}
+
+/// Word-based comparison of both the display and the original titles
+impl Default for TitleMeasure {
+    fn default() -> Self {
+        TitleMeasure {
+            tokens: true,
+            original_title: true,
+            string: StringMeasure::default(),
+        }
+    }
+}
+
+impl TitleMeasure {
+    /// Calculates the similarity of two titles
+    ///
+    /// # Arguments
+    /// * `a` - First title
+    /// * `b` - Second title
+    ///
+    /// # Returns
+    /// * `f32` - A value between 0.0 and 1.0
+    pub fn similarity(&self, a: &str, b: &str) -> f32 {
+        self.similarity_best(&[a], &[b])
+    }
+
+    /// Calculates the best similarity between any title of one movie and any
+    /// title of another (e.g. display and original titles)
+    ///
+    /// # Arguments
+    /// * `a` - Titles of the first movie
+    /// * `b` - Titles of the second movie
+    ///
+    /// # Returns
+    /// * `f32` - The highest pairwise similarity, 0.0 if either list has no
+    ///   title with words
+    pub fn similarity_best(&self, a: &[&str], b: &[&str]) -> f32 {
+        if !self.tokens {
+            return a
+                .iter()
+                .flat_map(|a| b.iter().map(move |b| self.string.similarity(a, b)))
+                .fold(0.0, f32::max);
+        }
+
+        let parse = |titles: &[&str]| -> Vec<TitleKey> {
+            titles
+                .iter()
+                .map(|title| TitleKey::parse(title))
+                .filter(|key| !key.is_empty())
+                .collect()
+        };
+        let (a, b) = (parse(a), parse(b));
+        similarity_best_key(a.iter(), b.iter()).unwrap_or(0.0)
+    }
+}
+
+/// Calculates the best similarity between any two already parsed titles
+///
+/// # Arguments
+/// * `a` - Parsed titles of the first movie
+/// * `b` - Parsed titles of the second movie
+///
+/// # Returns
+/// * `Option<f32>` - The highest pairwise `similarity_title`, or None if either
+///   movie has no title, so the title attribute is unknown
+pub fn similarity_best_key<'a>(
+    a: impl Iterator<Item = &'a TitleKey>,
+    b: impl Iterator<Item = &'a TitleKey> + Clone,
+) -> Option<f32> {
+    a.flat_map(|a| b.clone().map(move |b| similarity_title(a, b)))
+        .reduce(f32::max)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn titles_are_parsed_into_main_subtitle_and_installment() {
+        // Title, main title, subtitle and installment
+        type Case = (
+            &'static str,
+            &'static [&'static str],
+            &'static [&'static str],
+            Option<u32>,
+        );
+        let cases: &[Case] = &[
+            ("The Matrix", &["matrix"], &[], None),
+            ("Die Hard", &["die", "hard"], &[], None),
+            ("La La Land", &["la", "la", "land"], &[], None),
+            ("In Time", &["in", "time"], &[], None),
+            (
+                "E.T. the Extra-Terrestrial",
+                &["e", "t", "the", "extra", "terrestrial"],
+                &[],
+                None,
+            ),
+            ("A", &["a"], &[], None),
+            ("Rocky II", &["rocky"], &[], Some(2)),
+            ("Rocky V", &["rocky"], &[], Some(5)),
+            ("Alien³", &["alien"], &[], Some(3)),
+            ("Toy Story 3", &["toy", "story"], &[], Some(3)),
+            ("The Godfather: Part II", &["godfather"], &[], Some(2)),
+            ("Dune: Part Two", &["dune"], &[], Some(2)),
+            ("Kill Bill: Vol. 2", &["kill", "bill"], &[], Some(2)),
+            (
+                "Guardians of the Galaxy Vol. 2",
+                &["guardians", "of", "the", "galaxy"],
+                &[],
+                Some(2),
+            ),
+            (
+                "Mission: Impossible II",
+                &["mission"],
+                &["impossible"],
+                Some(2),
+            ),
+            ("Alien: Resurrection", &["alien"], &["resurrection"], None),
+            (
+                "Star Trek - The Motion Picture",
+                &["star", "trek"],
+                &["motion", "picture"],
+                None,
+            ),
+            ("Malcolm X", &["malcolm", "x"], &[], None),
+            ("Seven", &["seven"], &[], None),
+            ("2012", &["2012"], &[], None),
+            ("Blade Runner 2049", &["blade", "runner", "2049"], &[], None),
+            ("The The", &["the"], &[], None),
+            ("!!!", &[], &[], None),
+            ("", &[], &[], None),
+        ];
+        for &(title, main, subtitle, installment) in cases {
+            let key = TitleKey::parse(title);
+            assert_eq!(key.main(), main, "{}", title);
+            assert_eq!(key.subtitle(), subtitle, "{}", title);
+            assert_eq!(key.installment(), installment, "{}", title);
+        }
+    }
+
+    #[test]
+    fn titles_compare_by_franchise_and_words() {
+        let cases = [
+            ("The Matrix", "Matrix", 1.0),
+            ("The Matrix", "The Mummy", 0.0),
+            ("Rocky II", "Rocky 2", 1.0),
+            ("Rocky", "Rocky II", SAME_FRANCHISE),
+            ("Rocky", "Rocky 1", 1.0),
+            ("Rocky V", "Rocky II", SAME_FRANCHISE),
+            ("Alien", "Alien³", SAME_FRANCHISE),
+            ("Die Hard", "Hard Rain", 0.5),
+            ("Die Hard", "Hard Target", 0.5),
+            ("La La Land", "Land of the Lost", 2.0 / 7.0),
+            ("In Time", "Time", 2.0 / 3.0),
+            ("Die Another Day", "Another Day", 0.8),
+            ("Alien", "Aliens", SAME_FRANCHISE),
+            ("Aliens", "Alien: Resurrection", SAME_FRANCHISE),
+            ("Toy Story", "Toy Story 3", SAME_FRANCHISE),
+            ("The Godfather", "The Godfather: Part II", SAME_FRANCHISE),
+            ("Kill Bill: Vol. 1", "Kill Bill: Vol. 2", SAME_FRANCHISE),
+            (
+                "Guardians of the Galaxy",
+                "Guardians of the Galaxy Vol. 2",
+                SAME_FRANCHISE,
+            ),
+            ("Malcolm X", "Malcolm", 2.0 / 3.0),
+            ("Malcolm X", "Malcolm X", 1.0),
+            ("Seven", "Seven Samurai", 2.0 / 3.0),
+            ("Blade Runner", "Blade Runner 2049", 0.8),
+            ("Pirates of the Caribbean", "Pirate Radio", 1.0 / 3.0),
+            ("!!!", "???", 0.0),
+            ("", "", 0.0),
+        ];
+        for (a, b, expected) in cases {
+            let similarity = similarity_title(&TitleKey::parse(a), &TitleKey::parse(b));
+            assert!(
+                (similarity - expected).abs() < 1e-6,
+                "{} / {}: {}",
+                a,
+                b,
+                similarity
+            );
+            let reverse = similarity_title(&TitleKey::parse(b), &TitleKey::parse(a));
+            assert_eq!(similarity, reverse, "{} / {}", a, b);
+        }
+    }
+
+    #[test]
+    fn stem_matches_same_franchise() {
+        for (a, b) in [
+            ("Alien", "Aliens"),
+            ("Toy Story", "Toy Story 2"),
+            ("Cars", "Cars 3"),
+        ] {
+            let (a, b) = (TitleKey::parse(a), TitleKey::parse(b));
+            assert!(a.same_franchise(&b));
+            assert_eq!(a.stem(), b.stem());
+        }
+        let (boss, bus) = (TitleKey::parse("Boss"), TitleKey::parse("Bus"));
+        assert!(!boss.same_franchise(&bus));
+    }
+
+    #[test]
+    fn only_a_leading_article_of_the_title_language_is_dropped() {
+        let cases = [
+            ("Die Hard", "en", vec!["die", "hard"]),
+            ("Die Hard", "de", vec!["hard"]),
+            (
+                "Le Fabuleux Destin d'Amélie Poulain",
+                "en",
+                vec!["le", "fabuleux", "destin", "d", "amélie", "poulain"],
+            ),
+            (
+                "Le Fabuleux Destin d'Amélie Poulain",
+                "fr",
+                vec!["fabuleux", "destin", "d", "amélie", "poulain"],
+            ),
+            ("La Vita è Bella", "it", vec!["vita", "è", "bella"]),
+            ("Das Boot", "xx", vec!["das", "boot"]),
+            ("Der Untergang", "DE", vec!["untergang"]),
+        ];
+        for (title, language, main) in cases {
+            assert_eq!(
+                TitleKey::parse_in(title, language).main(),
+                main,
+                "{} ({})",
+                title,
+                language
+            );
+        }
+    }
+
+    #[test]
+    fn titles_without_words_are_unknown() {
+        let jaws = TitleKey::parse("Jaws");
+        assert!(TitleKey::parse("!!!").is_empty());
+        assert_eq!(
+            similarity_best_key([].into_iter(), [&jaws].into_iter()),
+            None
+        );
+        assert_eq!(TitleMeasure::default().similarity("!!!", "???"), 0.0);
+    }
+}